@@ -13,6 +13,8 @@ fn log_level() -> log::LevelFilter {
 
 #[cfg(not(feature = "coverage"))]
 fn build_app() -> tauri::Builder<tauri::Wry> {
+    use tauri::Manager;
+
     tauri::Builder::default().setup(|app| {
         if cfg!(debug_assertions) {
             app.handle().plugin(
@@ -21,6 +23,12 @@ fn build_app() -> tauri::Builder<tauri::Wry> {
                     .build(),
             )?;
         }
+        let app_data_dir = app.path().app_data_dir()?;
+        // A missing identity only matters once we connect, so it must not stop the app.
+        match mumble::IdentityStore::new(&app_data_dir).ensure_selected() {
+            Ok(identity) => log::info!("using client identity {}", identity.name),
+            Err(err) => log::error!("failed to prepare client identity: {err}"),
+        }
         Ok(())
     })
 }
@@ -0,0 +1,405 @@
+use crate::transport::errors::TransportError;
+use audiopus::coder::{Decoder, Encoder};
+use audiopus::{Application, Bitrate, Channels, SampleRate};
+use bytes::Bytes;
+use mumble_protocol_2x::voice::{Clientbound, Serverbound, VoicePacket, VoicePacketPayload};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+pub const SAMPLE_RATE: u32 = 48_000;
+pub const DEFAULT_BITRATE: i32 = 40_000;
+
+const SAMPLES_PER_TICK: usize = SAMPLE_RATE as usize / 100;
+const MAX_OPUS_PACKET: usize = 1275 * 3;
+// Opus packets never carry more than 120 ms of audio.
+const MAX_DECODED_SAMPLES: usize = SAMPLES_PER_TICK * 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameDuration {
+    Ms10,
+    Ms20,
+    Ms40,
+    Ms60,
+}
+
+impl FrameDuration {
+    pub fn from_millis(millis: u32) -> Result<Self, TransportError> {
+        match millis {
+            10 => Ok(FrameDuration::Ms10),
+            20 => Ok(FrameDuration::Ms20),
+            40 => Ok(FrameDuration::Ms40),
+            60 => Ok(FrameDuration::Ms60),
+            _ => Err(TransportError::InvalidConfig(format!(
+                "unsupported audio frame duration: {millis} ms"
+            ))),
+        }
+    }
+
+    pub fn millis(self) -> u32 {
+        match self {
+            FrameDuration::Ms10 => 10,
+            FrameDuration::Ms20 => 20,
+            FrameDuration::Ms40 => 40,
+            FrameDuration::Ms60 => 60,
+        }
+    }
+
+    pub fn samples(self) -> usize {
+        SAMPLES_PER_TICK * self.ticks() as usize
+    }
+
+    fn ticks(self) -> u64 {
+        // Mumble sequence numbers count 10 ms steps, not packets.
+        u64::from(self.millis() / 10)
+    }
+}
+
+#[derive(Debug)]
+pub struct VoiceEncoder {
+    encoder: Encoder,
+    frame: FrameDuration,
+    target: u8,
+    seq_num: u64,
+    pending: Vec<i16>,
+}
+
+impl VoiceEncoder {
+    pub fn new(frame: FrameDuration, bitrate: i32) -> Result<Self, TransportError> {
+        let mut encoder = Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip)
+            .map_err(|err| codec_error("encoder setup", err))?;
+        encoder
+            .set_bitrate(Bitrate::BitsPerSecond(bitrate))
+            .map_err(|err| codec_error("encoder setup", err))?;
+        Ok(Self {
+            encoder,
+            frame,
+            target: 0,
+            seq_num: 0,
+            pending: Vec::with_capacity(frame.samples()),
+        })
+    }
+
+    pub fn frame(&self) -> FrameDuration {
+        self.frame
+    }
+
+    pub fn seq_num(&self) -> u64 {
+        self.seq_num
+    }
+
+    pub fn set_target(&mut self, target: u8) {
+        self.target = target;
+    }
+
+    pub fn push(&mut self, pcm: &[i16]) -> Result<Vec<VoicePacket<Serverbound>>, TransportError> {
+        // Capture callbacks rarely line up with frame boundaries, so keep the remainder
+        // around for the next call.
+        self.pending.extend_from_slice(pcm);
+        let frame = self.frame.samples();
+        let mut packets = Vec::new();
+        while self.pending.len() >= frame {
+            let samples: Vec<i16> = self.pending.drain(..frame).collect();
+            packets.push(self.encode(&samples, false)?);
+        }
+        Ok(packets)
+    }
+
+    pub fn finish(&mut self) -> Result<VoicePacket<Serverbound>, TransportError> {
+        // The last frame is padded with silence and tells receivers to stop playback.
+        let mut samples = std::mem::take(&mut self.pending);
+        samples.resize(self.frame.samples(), 0);
+        self.encode(&samples, true)
+    }
+
+    fn encode(
+        &mut self,
+        samples: &[i16],
+        end_of_transmission: bool,
+    ) -> Result<VoicePacket<Serverbound>, TransportError> {
+        let mut output = [0u8; MAX_OPUS_PACKET];
+        let len = self
+            .encoder
+            .encode(samples, &mut output)
+            .map_err(|err| codec_error("encode", err))?;
+        let packet = VoicePacket::Audio {
+            _dst: PhantomData,
+            target: self.target,
+            session_id: (),
+            seq_num: self.seq_num,
+            payload: VoicePacketPayload::Opus(
+                Bytes::copy_from_slice(&output[..len]),
+                end_of_transmission,
+            ),
+            position_info: None,
+        };
+        self.seq_num += self.frame.ticks();
+        Ok(packet)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedAudio {
+    pub session_id: u32,
+    pub seq_num: u64,
+    pub pcm: Vec<i16>,
+    pub end_of_transmission: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct VoiceDecoder {
+    sessions: HashMap<u32, Decoder>,
+}
+
+impl VoiceDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn remove(&mut self, session_id: u32) {
+        self.sessions.remove(&session_id);
+    }
+
+    pub fn decode(
+        &mut self,
+        packet: &VoicePacket<Clientbound>,
+    ) -> Result<Option<DecodedAudio>, TransportError> {
+        let VoicePacket::Audio {
+            session_id,
+            seq_num,
+            payload,
+            ..
+        } = packet
+        else {
+            return Ok(None);
+        };
+        let VoicePacketPayload::Opus(bytes, end_of_transmission) = payload else {
+            return Err(TransportError::Protocol(
+                "unsupported voice codec, only opus is handled".to_string(),
+            ));
+        };
+
+        let pcm = if bytes.is_empty() {
+            Vec::new()
+        } else {
+            // Opus decoders carry state between frames, so every speaker gets its own.
+            let decoder = match self.sessions.entry(*session_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    Decoder::new(SampleRate::Hz48000, Channels::Mono)
+                        .map_err(|err| codec_error("decoder setup", err))?,
+                ),
+            };
+            let mut pcm = vec![0i16; MAX_DECODED_SAMPLES];
+            let len = decoder
+                .decode(Some(&bytes[..]), pcm.as_mut_slice(), false)
+                .map_err(|err| codec_error("decode", err))?;
+            pcm.truncate(len);
+            pcm
+        };
+
+        if *end_of_transmission {
+            self.sessions.remove(session_id);
+        }
+        Ok(Some(DecodedAudio {
+            session_id: *session_id,
+            seq_num: *seq_num,
+            pcm,
+            end_of_transmission: *end_of_transmission,
+        }))
+    }
+}
+
+fn codec_error(context: &str, err: audiopus::Error) -> TransportError {
+    TransportError::Protocol(format!("opus {context} failed: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodedAudio, FrameDuration, VoiceDecoder, VoiceEncoder, DEFAULT_BITRATE};
+    use crate::transport::errors::TransportError;
+    use bytes::Bytes;
+    use mumble_protocol_2x::voice::{Clientbound, Serverbound, VoicePacket, VoicePacketPayload};
+    use std::marker::PhantomData;
+
+    fn sine(samples: usize) -> Vec<i16> {
+        (0..samples)
+            .map(|n| {
+                let phase = n as f64 * 440.0 * std::f64::consts::TAU / 48_000.0;
+                (phase.sin() * 8_000.0) as i16
+            })
+            .collect()
+    }
+
+    fn rms(pcm: &[i16]) -> f64 {
+        let sum: f64 = pcm.iter().map(|&s| f64::from(s) * f64::from(s)).sum();
+        (sum / pcm.len().max(1) as f64).sqrt()
+    }
+
+    fn relay(packet: VoicePacket<Serverbound>, session_id: u32) -> VoicePacket<Clientbound> {
+        // Stands in for the server, which stamps the sender's session on forwarded audio.
+        let VoicePacket::Audio {
+            target,
+            seq_num,
+            payload,
+            position_info,
+            ..
+        } = packet
+        else {
+            panic!("expected audio, got {packet:?}");
+        };
+        VoicePacket::Audio {
+            _dst: PhantomData,
+            target,
+            session_id,
+            seq_num,
+            payload,
+            position_info,
+        }
+    }
+
+    fn opus(session_id: u32, payload: &'static [u8], end: bool) -> VoicePacket<Clientbound> {
+        VoicePacket::Audio {
+            _dst: PhantomData,
+            target: 0,
+            session_id,
+            seq_num: 0,
+            payload: VoicePacketPayload::Opus(Bytes::from_static(payload), end),
+            position_info: None,
+        }
+    }
+
+    /// Synthetic PCM survives encoding and decoding at every supported frame size.
+    #[test]
+    fn pipeline_round_trips_pcm() {
+        for frame in [
+            FrameDuration::Ms10,
+            FrameDuration::Ms20,
+            FrameDuration::Ms40,
+            FrameDuration::Ms60,
+        ] {
+            // Arrange
+            let input = sine(48_000);
+            let mut encoder = VoiceEncoder::new(frame, DEFAULT_BITRATE).expect("encoder failed");
+            let mut decoder = VoiceDecoder::new();
+
+            // Act
+            let mut packets = Vec::new();
+            for chunk in input.chunks(700) {
+                packets.extend(encoder.push(chunk).expect("encode failed"));
+            }
+            packets.push(encoder.finish().expect("finish failed"));
+            let decoded: Vec<DecodedAudio> = packets
+                .into_iter()
+                .map(|packet| {
+                    decoder
+                        .decode(&relay(packet, 9))
+                        .expect("decode failed")
+                        .expect("missing audio")
+                })
+                .collect();
+
+            // Assert
+            let frames = 48_000 / frame.samples();
+            assert_eq!(decoded.len(), frames + 1, "{frame:?}");
+            assert!(decoded.iter().all(|audio| audio.session_id == 9));
+            assert!(decoded
+                .iter()
+                .all(|audio| audio.pcm.len() == frame.samples()));
+            let seq_nums: Vec<u64> = decoded.iter().map(|audio| audio.seq_num).collect();
+            let step = u64::from(frame.millis() / 10);
+            let expected: Vec<u64> = (0..=frames as u64).map(|n| n * step).collect();
+            assert_eq!(seq_nums, expected);
+            let pcm: Vec<i16> = decoded[..frames]
+                .iter()
+                .flat_map(|audio| audio.pcm.iter().copied())
+                .collect();
+            let ratio = rms(&pcm[4_800..]) / rms(&input[4_800..]);
+            assert!((0.5..1.5).contains(&ratio), "{frame:?} ratio {ratio}");
+            assert!(decoded
+                .last()
+                .is_some_and(|audio| audio.end_of_transmission));
+            assert!(decoded[..frames]
+                .iter()
+                .all(|audio| !audio.end_of_transmission));
+            assert_eq!(decoder.active_sessions(), 0);
+        }
+    }
+
+    /// Each remote session decodes independently and is released when it stops talking.
+    #[test]
+    fn decoder_tracks_sessions_separately() {
+        // Arrange
+        let mut alice =
+            VoiceEncoder::new(FrameDuration::Ms20, DEFAULT_BITRATE).expect("encoder failed");
+        let mut bob =
+            VoiceEncoder::new(FrameDuration::Ms20, DEFAULT_BITRATE).expect("encoder failed");
+        let mut decoder = VoiceDecoder::new();
+        let pcm = sine(960);
+
+        // Act
+        let first = alice.push(&pcm).expect("encode failed").remove(0);
+        let second = bob.push(&pcm).expect("encode failed").remove(0);
+        decoder.decode(&relay(first, 1)).expect("decode failed");
+        decoder.decode(&relay(second, 2)).expect("decode failed");
+        let active = decoder.active_sessions();
+        let end = alice.finish().expect("finish failed");
+        decoder.decode(&relay(end, 1)).expect("decode failed");
+
+        // Assert
+        assert_eq!(active, 2);
+        assert_eq!(decoder.active_sessions(), 1);
+        decoder.remove(2);
+        assert_eq!(decoder.active_sessions(), 0);
+    }
+
+    /// Pings and empty terminators decode without audio, while corrupt frames are errors.
+    #[test]
+    fn decoder_handles_non_audio_and_corrupt_frames() {
+        // Arrange
+        let mut decoder = VoiceDecoder::new();
+        let ping = VoicePacket::Ping { timestamp: 1 };
+
+        // Act
+        let pinged = decoder.decode(&ping).expect("decode failed");
+        let terminator = decoder
+            .decode(&opus(3, b"", true))
+            .expect("decode failed")
+            .expect("missing audio");
+        let corrupt = decoder.decode(&opus(3, &[0xff, 0x00], false));
+
+        // Assert
+        assert_eq!(pinged, None);
+        assert!(terminator.pcm.is_empty());
+        assert!(terminator.end_of_transmission);
+        assert!(matches!(corrupt, Err(TransportError::Protocol(_))));
+    }
+
+    /// Only the frame sizes Mumble clients use are accepted.
+    #[test]
+    fn frame_duration_from_millis() {
+        // Arrange
+        let valid = [10, 20, 40, 60];
+
+        // Act
+        let frames: Vec<FrameDuration> = valid
+            .iter()
+            .map(|&millis| FrameDuration::from_millis(millis).expect("valid duration"))
+            .collect();
+        let invalid = FrameDuration::from_millis(30);
+
+        // Assert
+        assert_eq!(
+            frames
+                .iter()
+                .map(|frame| frame.samples())
+                .collect::<Vec<_>>(),
+            vec![480, 960, 1920, 2880]
+        );
+        assert!(matches!(invalid, Err(TransportError::InvalidConfig(_))));
+    }
+}
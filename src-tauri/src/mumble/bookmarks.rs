@@ -0,0 +1,153 @@
+use crate::mumble::config::MumbleConfig;
+use crate::mumble::tls::TlsPolicy;
+use crate::mumble::vault::{write_private, CredentialVault};
+use crate::transport::errors::TransportError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub server: String,
+    pub port: u16,
+    pub username: String,
+    #[serde(default)]
+    pub tls: TlsPolicy,
+}
+
+impl Bookmark {
+    pub fn config(&self, vault: Option<&CredentialVault>) -> MumbleConfig {
+        let mut config = MumbleConfig::new(self.server.clone(), self.port, self.username.clone());
+        config.tls = self.tls.clone();
+        if let Some(vault) = vault {
+            vault.apply_to(&mut config);
+        }
+        config
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BookmarkStore {
+    path: PathBuf,
+    bookmarks: Vec<Bookmark>,
+}
+
+impl BookmarkStore {
+    pub fn load(app_data_dir: &Path) -> Result<Self, TransportError> {
+        let path = app_data_dir.join(BOOKMARKS_FILE);
+        let bookmarks = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents).map_err(|err| {
+                TransportError::InvalidConfig(format!("invalid bookmarks: {err}"))
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self { path, bookmarks })
+    }
+
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    pub fn bookmark(&self, name: &str) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|bookmark| bookmark.name == name)
+    }
+
+    pub fn upsert(&mut self, bookmark: Bookmark) -> Result<(), TransportError> {
+        match self
+            .bookmarks
+            .iter_mut()
+            .find(|existing| existing.name == bookmark.name)
+        {
+            Some(existing) => *existing = bookmark,
+            None => self.bookmarks.push(bookmark),
+        }
+        self.save()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), TransportError> {
+        self.bookmarks.retain(|bookmark| bookmark.name != name);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), TransportError> {
+        let contents = serde_json::to_vec_pretty(&self.bookmarks)
+            .map_err(|err| TransportError::Io(format!("bookmark encoding failed: {err}")))?;
+        write_private(&self.path, &contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bookmark, BookmarkStore};
+    use crate::mumble::identity::tests::TempDir;
+    use crate::mumble::tls::TlsPolicy;
+    use crate::mumble::vault::tests::open_machine_vault;
+    use crate::mumble::vault::Credentials;
+
+    fn bookmark(name: &str) -> Bookmark {
+        Bookmark {
+            name: name.to_string(),
+            server: "voice.example".to_string(),
+            port: 64738,
+            username: "alice".to_string(),
+            tls: TlsPolicy::default(),
+        }
+    }
+
+    /// Bookmarks persist and are replaced by name.
+    #[test]
+    fn upsert_persists_and_replaces() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut store = BookmarkStore::load(&dir.0).expect("load failed");
+        store.upsert(bookmark("team")).expect("save failed");
+        let mut renamed = bookmark("team");
+        renamed.username = "bob".to_string();
+        renamed.tls.verify_system_cas = false;
+
+        // Act
+        store.upsert(renamed.clone()).expect("save failed");
+        store.upsert(bookmark("other")).expect("save failed");
+        store.remove("other").expect("remove failed");
+        let reloaded = BookmarkStore::load(&dir.0).expect("reload failed");
+
+        // Assert
+        assert_eq!(reloaded.bookmarks(), [renamed]);
+    }
+
+    /// Connecting from a bookmark pulls the password and tokens out of the vault.
+    #[test]
+    fn config_fills_credentials_from_vault() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut vault = open_machine_vault(&dir.0).expect("open failed");
+        vault
+            .set_credentials(
+                "voice.example",
+                64738,
+                Credentials {
+                    password: Some("hunter2".into()),
+                    tokens: vec!["red".into()],
+                },
+            )
+            .expect("save failed");
+        let mut bookmark = bookmark("team");
+        bookmark.tls.ca_bundle = Some("/etc/babble/team-ca.pem".into());
+
+        // Act
+        let with_vault = bookmark.config(Some(&vault));
+        let without_vault = bookmark.config(None);
+
+        // Assert
+        assert_eq!(with_vault.server, "voice.example");
+        assert_eq!(with_vault.username, "alice");
+        assert_eq!(with_vault.password, Some("hunter2".into()));
+        assert_eq!(with_vault.tokens, vec!["red"]);
+        assert!(without_vault.password.is_none());
+        assert_eq!(with_vault.tls, bookmark.tls);
+    }
+}
@@ -0,0 +1,474 @@
+use crate::mumble::secret::{SecretBytes, SecretString};
+use crate::transport::errors::TransportError;
+use openssl::asn1::{Asn1Integer, Asn1Time};
+use openssl::bn::{BigNum, MsbOption};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::{PKey, Private};
+use openssl::provider::Provider;
+use openssl::rsa::Rsa;
+use openssl::ssl::SslConnectorBuilder;
+use openssl::stack::Stack;
+use openssl::x509::{X509NameBuilder, X509};
+use std::sync::Once;
+
+const GENERATED_KEY_BITS: u32 = 2048;
+const GENERATED_VALID_DAYS: u32 = 20 * 365;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClientCertificate {
+    Pem {
+        bundle: SecretString,
+        password: Option<SecretString>,
+    },
+    Pkcs12 {
+        der: SecretBytes,
+        password: Option<SecretString>,
+    },
+}
+
+pub struct ClientIdentity {
+    certificate: X509,
+    chain: Vec<X509>,
+    key: PKey<Private>,
+}
+
+impl ClientIdentity {
+    pub fn load(source: &ClientCertificate) -> Result<Self, TransportError> {
+        match source {
+            ClientCertificate::Pem { bundle, password } => Self::from_pem(
+                bundle.expose().as_bytes(),
+                password.as_ref().map(SecretString::expose),
+            ),
+            ClientCertificate::Pkcs12 { der, password } => {
+                Self::from_pkcs12(der.expose(), password.as_ref().map(SecretString::expose))
+            }
+        }
+    }
+
+    pub fn from_pem(bundle: &[u8], password: Option<&str>) -> Result<Self, TransportError> {
+        let mut certificates = X509::stack_from_pem(bundle)
+            .map_err(|err| invalid(format!("invalid client certificate: {err}")))?
+            .into_iter();
+        let certificate = certificates
+            .next()
+            .ok_or_else(|| invalid("client certificate bundle has no certificate".to_string()))?;
+        let key = match password {
+            Some(password) => PKey::private_key_from_pem_passphrase(bundle, password.as_bytes()),
+            None => PKey::private_key_from_pem(bundle),
+        }
+        .map_err(|err| invalid(format!("invalid client private key: {err}")))?;
+        Self::new(certificate, certificates.collect(), key)
+    }
+
+    pub fn from_pkcs12(der: &[u8], password: Option<&str>) -> Result<Self, TransportError> {
+        load_legacy_provider();
+        let parsed = Pkcs12::from_der(der)
+            .and_then(|archive| archive.parse2(password.unwrap_or("")))
+            .map_err(|err| invalid(format!("invalid PKCS#12 client certificate: {err}")))?;
+        let certificate = parsed
+            .cert
+            .ok_or_else(|| invalid("PKCS#12 archive has no certificate".to_string()))?;
+        let key = parsed
+            .pkey
+            .ok_or_else(|| invalid("PKCS#12 archive has no private key".to_string()))?;
+        let chain = parsed
+            .ca
+            .map(|stack| stack.into_iter().collect())
+            .unwrap_or_default();
+        Self::new(certificate, chain, key)
+    }
+
+    pub fn generate(common_name: &str) -> Result<Self, TransportError> {
+        let certificate_error =
+            |err| TransportError::Io(format!("certificate generation failed: {err}"));
+        let key = Rsa::generate(GENERATED_KEY_BITS)
+            .and_then(PKey::from_rsa)
+            .map_err(certificate_error)?;
+        let mut subject = X509NameBuilder::new().map_err(certificate_error)?;
+        subject
+            .append_entry_by_text("CN", common_name)
+            .map_err(|err| invalid(format!("invalid identity name: {err}")))?;
+        let subject = subject.build();
+
+        let mut serial = BigNum::new().map_err(certificate_error)?;
+        serial
+            .rand(128, MsbOption::MAYBE_ZERO, false)
+            .map_err(certificate_error)?;
+        let serial = Asn1Integer::from_bn(&serial).map_err(certificate_error)?;
+        let not_before = Asn1Time::days_from_now(0).map_err(certificate_error)?;
+        let not_after = Asn1Time::days_from_now(GENERATED_VALID_DAYS).map_err(certificate_error)?;
+
+        let mut builder = X509::builder().map_err(certificate_error)?;
+        builder.set_version(2).map_err(certificate_error)?;
+        builder
+            .set_serial_number(&serial)
+            .map_err(certificate_error)?;
+        builder
+            .set_subject_name(&subject)
+            .map_err(certificate_error)?;
+        builder
+            .set_issuer_name(&subject)
+            .map_err(certificate_error)?;
+        builder.set_pubkey(&key).map_err(certificate_error)?;
+        builder
+            .set_not_before(&not_before)
+            .map_err(certificate_error)?;
+        builder
+            .set_not_after(&not_after)
+            .map_err(certificate_error)?;
+        builder
+            .sign(&key, MessageDigest::sha256())
+            .map_err(certificate_error)?;
+        Self::new(builder.build(), Vec::new(), key)
+    }
+
+    pub fn to_pem(&self) -> Result<SecretString, TransportError> {
+        let encode_error = |err| TransportError::Io(format!("certificate encoding failed: {err}"));
+        let mut bundle = self.certificate.to_pem().map_err(encode_error)?;
+        for certificate in &self.chain {
+            bundle.extend(certificate.to_pem().map_err(encode_error)?);
+        }
+        bundle.extend(self.key.private_key_to_pem_pkcs8().map_err(encode_error)?);
+        String::from_utf8(bundle)
+            .map(SecretString::from)
+            .map_err(|err| TransportError::Io(format!("certificate encoding failed: {err}")))
+    }
+
+    pub fn to_pkcs12(&self, name: &str, password: Option<&str>) -> Result<Vec<u8>, TransportError> {
+        let encode_error = |err| TransportError::Io(format!("PKCS#12 export failed: {err}"));
+        let mut chain = Stack::new().map_err(encode_error)?;
+        for certificate in &self.chain {
+            chain.push(certificate.clone()).map_err(encode_error)?;
+        }
+        // SHA-1/3DES is what the official client and older OpenSSL builds can read back.
+        Pkcs12::builder()
+            .name(name)
+            .pkey(&self.key)
+            .cert(&self.certificate)
+            .ca(chain)
+            .key_algorithm(Nid::PBE_WITHSHA1AND3_KEY_TRIPLEDES_CBC)
+            .cert_algorithm(Nid::PBE_WITHSHA1AND3_KEY_TRIPLEDES_CBC)
+            .mac_md(MessageDigest::sha1())
+            .build2(password.unwrap_or(""))
+            .and_then(|archive| archive.to_der())
+            .map_err(encode_error)
+    }
+
+    pub fn certificate(&self) -> &X509 {
+        &self.certificate
+    }
+
+    pub fn chain(&self) -> &[X509] {
+        &self.chain
+    }
+
+    pub fn apply(&self, builder: &mut SslConnectorBuilder) -> Result<(), TransportError> {
+        builder
+            .set_certificate(&self.certificate)
+            .and_then(|_| builder.set_private_key(&self.key))
+            .map_err(|err| invalid(format!("client certificate rejected: {err}")))?;
+        for certificate in &self.chain {
+            builder
+                .add_extra_chain_cert(certificate.clone())
+                .map_err(|err| invalid(format!("client certificate chain rejected: {err}")))?;
+        }
+        Ok(())
+    }
+
+    fn new(
+        certificate: X509,
+        chain: Vec<X509>,
+        key: PKey<Private>,
+    ) -> Result<Self, TransportError> {
+        let public_key = certificate
+            .public_key()
+            .map_err(|err| invalid(format!("invalid client certificate: {err}")))?;
+        if !key.public_eq(&public_key) {
+            return Err(invalid(
+                "client private key does not match certificate".to_string(),
+            ));
+        }
+        Ok(Self {
+            certificate,
+            chain,
+            key,
+        })
+    }
+}
+
+fn load_legacy_provider() {
+    static LEGACY: Once = Once::new();
+    // Older Mumble backups encrypt certificates with RC2, which OpenSSL 3 only ships as "legacy".
+    LEGACY.call_once(|| {
+        if let Ok(provider) = Provider::try_load(None, "legacy", true) {
+            std::mem::forget(provider);
+        }
+    });
+}
+
+fn invalid(message: String) -> TransportError {
+    TransportError::InvalidConfig(message)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{ClientCertificate, ClientIdentity};
+    use crate::transport::errors::TransportError;
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkcs12::Pkcs12;
+    use openssl::pkey::{PKey, Private};
+    use openssl::rsa::Rsa;
+    use openssl::ssl::{SslConnector, SslMethod};
+    use openssl::symm::Cipher;
+    use openssl::x509::{X509NameBuilder, X509};
+
+    pub(crate) fn self_signed(name: &str) -> (X509, PKey<Private>) {
+        let key = PKey::from_rsa(Rsa::generate(2048).expect("rsa failed")).expect("pkey failed");
+        let mut subject = X509NameBuilder::new().expect("name failed");
+        subject.append_entry_by_text("CN", name).expect("cn failed");
+        let subject = subject.build();
+        let mut builder = X509::builder().expect("builder failed");
+        builder.set_version(2).expect("version failed");
+        builder.set_subject_name(&subject).expect("subject failed");
+        builder.set_issuer_name(&subject).expect("issuer failed");
+        builder.set_pubkey(&key).expect("pubkey failed");
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).expect("time failed"))
+            .expect("not before failed");
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).expect("time failed"))
+            .expect("not after failed");
+        builder
+            .sign(&key, MessageDigest::sha256())
+            .expect("sign failed");
+        (builder.build(), key)
+    }
+
+    fn pem_bundle(certificate: &X509, key: &PKey<Private>) -> String {
+        let mut bundle = certificate.to_pem().expect("cert pem failed");
+        bundle.extend(key.private_key_to_pem_pkcs8().expect("key pem failed"));
+        String::from_utf8(bundle).expect("pem is not utf-8")
+    }
+
+    /// A PEM bundle with a certificate and matching key loads.
+    #[test]
+    fn load_accepts_pem_bundle() {
+        // Arrange
+        let (certificate, key) = self_signed("alice");
+        let source = ClientCertificate::Pem {
+            bundle: pem_bundle(&certificate, &key).into(),
+            password: None,
+        };
+
+        // Act
+        let identity = ClientIdentity::load(&source).expect("load failed");
+
+        // Assert
+        assert_eq!(
+            identity.certificate().to_der().expect("der failed"),
+            certificate.to_der().expect("der failed")
+        );
+        assert!(identity.chain().is_empty());
+    }
+
+    /// Encrypted PEM keys are decrypted with the configured password.
+    #[test]
+    fn load_accepts_encrypted_pem_key() {
+        // Arrange
+        let (certificate, key) = self_signed("alice");
+        let mut bundle = certificate.to_pem().expect("cert pem failed");
+        bundle.extend(
+            key.private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), b"secret")
+                .expect("key pem failed"),
+        );
+        let source = ClientCertificate::Pem {
+            bundle: String::from_utf8(bundle).expect("pem is not utf-8").into(),
+            password: Some("secret".into()),
+        };
+
+        // Act
+        let result = ClientIdentity::load(&source);
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    /// PKCS#12 archives load with their password.
+    #[test]
+    fn load_accepts_pkcs12() {
+        // Arrange
+        let (certificate, key) = self_signed("alice");
+        let der = Pkcs12::builder()
+            .name("alice")
+            .pkey(&key)
+            .cert(&certificate)
+            .build2("pw")
+            .expect("pkcs12 failed")
+            .to_der()
+            .expect("der failed");
+
+        // Act
+        let loaded = ClientIdentity::load(&ClientCertificate::Pkcs12 {
+            der: der.clone().into(),
+            password: Some("pw".into()),
+        });
+        let wrong = ClientIdentity::load(&ClientCertificate::Pkcs12 {
+            der: der.into(),
+            password: Some("nope".into()),
+        });
+
+        // Assert
+        assert!(loaded.is_ok());
+        assert!(matches!(wrong, Err(TransportError::InvalidConfig(_))));
+    }
+
+    /// Bundles without a key, or with a key for another certificate, are rejected.
+    #[test]
+    fn load_rejects_incomplete_or_mismatched_pem() {
+        // Arrange
+        let (certificate, _) = self_signed("alice");
+        let (_, other_key) = self_signed("bob");
+        let cert_only =
+            String::from_utf8(certificate.to_pem().expect("pem failed")).expect("pem is not utf-8");
+
+        // Act
+        let missing_key = ClientIdentity::load(&ClientCertificate::Pem {
+            bundle: cert_only.into(),
+            password: None,
+        });
+        let mismatched = ClientIdentity::load(&ClientCertificate::Pem {
+            bundle: pem_bundle(&certificate, &other_key).into(),
+            password: None,
+        });
+        let garbage = ClientIdentity::load(&ClientCertificate::Pem {
+            bundle: "not a certificate".into(),
+            password: None,
+        });
+
+        // Assert
+        assert!(matches!(missing_key, Err(TransportError::InvalidConfig(_))));
+        assert!(matches!(mismatched, Err(TransportError::InvalidConfig(_))));
+        assert!(matches!(garbage, Err(TransportError::InvalidConfig(_))));
+    }
+
+    /// Generated identities are self-signed for the given name and round-trip through PEM.
+    #[test]
+    fn generate_creates_self_signed_identity() {
+        // Arrange
+        // Act
+        let identity = ClientIdentity::generate("alice").expect("generate failed");
+        let reloaded = ClientIdentity::load(&ClientCertificate::Pem {
+            bundle: identity.to_pem().expect("pem failed"),
+            password: None,
+        })
+        .expect("reload failed");
+
+        // Assert
+        let certificate = reloaded.certificate();
+        let common_name = certificate
+            .subject_name()
+            .entries()
+            .next()
+            .expect("missing subject entry")
+            .data()
+            .as_utf8()
+            .expect("invalid subject")
+            .to_string();
+        assert_eq!(common_name, "alice");
+        assert_eq!(
+            certificate.issuer_name().to_der().expect("der failed"),
+            certificate.subject_name().to_der().expect("der failed")
+        );
+        let public_key = certificate.public_key().expect("missing key");
+        assert!(certificate.verify(&public_key).expect("verify failed"));
+    }
+
+    /// Exported archives load back with the same certificate and password.
+    #[test]
+    fn to_pkcs12_round_trips() {
+        // Arrange
+        let identity = ClientIdentity::generate("alice").expect("generate failed");
+
+        // Act
+        let der = identity
+            .to_pkcs12("alice", Some("pw"))
+            .expect("export failed");
+        let reloaded = ClientIdentity::from_pkcs12(&der, Some("pw")).expect("import failed");
+
+        // Assert
+        assert_eq!(
+            reloaded.certificate().to_der().expect("der failed"),
+            identity.certificate().to_der().expect("der failed")
+        );
+    }
+
+    /// Archives using RC2 certificate encryption, as older Mumble clients write them, still load.
+    #[test]
+    fn from_pkcs12_reads_legacy_rc2_archives() {
+        // Arrange
+        super::load_legacy_provider();
+        let (certificate, key) = self_signed("alice");
+        let der = Pkcs12::builder()
+            .name("alice")
+            .pkey(&key)
+            .cert(&certificate)
+            .cert_algorithm(openssl::nid::Nid::PBE_WITHSHA1AND40BITRC2_CBC)
+            .key_algorithm(openssl::nid::Nid::PBE_WITHSHA1AND3_KEY_TRIPLEDES_CBC)
+            .mac_md(MessageDigest::sha1())
+            .build2("")
+            .expect("pkcs12 failed")
+            .to_der()
+            .expect("der failed");
+
+        // Act
+        let result = ClientIdentity::from_pkcs12(&der, None);
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    /// A checked-in RC2/3DES archive, made the way the reference client exports, imports intact.
+    #[test]
+    fn from_pkcs12_imports_legacy_fixture() {
+        // Arrange
+        let der = include_bytes!("fixtures/legacy_identity.p12");
+        let mut builder = SslConnector::builder(SslMethod::tls()).expect("builder failed");
+
+        // Act
+        let identity = ClientIdentity::from_pkcs12(der, None).expect("import failed");
+        identity.apply(&mut builder).expect("apply failed");
+
+        // Assert
+        let common_name = identity
+            .certificate()
+            .subject_name()
+            .entries_by_nid(openssl::nid::Nid::COMMONNAME)
+            .next()
+            .and_then(|entry| entry.data().as_utf8().ok())
+            .map(|name| name.to_string());
+        assert_eq!(common_name.as_deref(), Some("legacy-user"));
+        assert!(builder.check_private_key().is_ok());
+    }
+
+    /// Loaded identities can be installed on a TLS connector.
+    #[test]
+    fn apply_installs_identity_on_connector() {
+        // Arrange
+        let (certificate, key) = self_signed("alice");
+        let identity = ClientIdentity::load(&ClientCertificate::Pem {
+            bundle: pem_bundle(&certificate, &key).into(),
+            password: None,
+        })
+        .expect("load failed");
+        let mut builder = SslConnector::builder(SslMethod::tls()).expect("builder failed");
+
+        // Act
+        identity.apply(&mut builder).expect("apply failed");
+
+        // Assert
+        assert!(builder.check_private_key().is_ok());
+    }
+}
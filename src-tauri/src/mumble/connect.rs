@@ -0,0 +1,264 @@
+use crate::mumble::proxy::ProxyConfig;
+use crate::transport::errors::{ConnectPhase, TransportError};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(15);
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnectTimeouts {
+    pub connect: Duration,
+    pub handshake: Duration,
+}
+
+impl Default for ConnectTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: DEFAULT_CONNECT_TIMEOUT,
+            handshake: DEFAULT_HANDSHAKE_TIMEOUT,
+        }
+    }
+}
+
+pub fn strip_brackets(server: &str) -> &str {
+    let server = server.trim();
+    server
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(server)
+}
+
+pub fn resolve(server: &str, port: u16) -> Result<Vec<SocketAddr>, TransportError> {
+    let host = strip_brackets(server);
+    let addresses: Vec<SocketAddr> = (host, port)
+        .to_socket_addrs()
+        .map_err(|err| connect_error(ConnectPhase::Resolve, format!("{host}: {err}")))?
+        .collect();
+    if addresses.is_empty() {
+        return Err(connect_error(
+            ConnectPhase::Resolve,
+            format!("{host}: no addresses found"),
+        ));
+    }
+    Ok(addresses)
+}
+
+pub fn interleave_families(addresses: &[SocketAddr]) -> Vec<SocketAddr> {
+    let Some(first) = addresses.first() else {
+        return Vec::new();
+    };
+    let (preferred, other): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses
+        .iter()
+        .partition(|address| address.is_ipv6() == first.is_ipv6());
+
+    let mut ordered = Vec::with_capacity(addresses.len());
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+pub fn connect_happy_eyeballs(
+    addresses: &[SocketAddr],
+    timeout: Duration,
+    attempt_delay: Duration,
+) -> Result<TcpStream, TransportError> {
+    // RFC 8305: alternate address families and start a new attempt whenever the previous one
+    // fails or has been pending for `attempt_delay`, keeping whichever connects first.
+    let ordered = interleave_families(addresses);
+    let deadline = Instant::now() + timeout;
+    let (results_tx, results) = mpsc::channel();
+    let mut next = 0;
+    let mut pending = 0;
+    let mut last_error = None;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(connect_error(
+                ConnectPhase::Tcp,
+                "connection timed out".to_string(),
+            ));
+        }
+
+        if let Some(&address) = ordered.get(next) {
+            next += 1;
+            pending += 1;
+            let results_tx = results_tx.clone();
+            std::thread::Builder::new()
+                .name("mumble-connect".to_string())
+                .spawn(move || {
+                    let _ =
+                        results_tx.send((address, TcpStream::connect_timeout(&address, remaining)));
+                })?;
+        }
+        if pending == 0 {
+            break;
+        }
+
+        let wait = if next < ordered.len() {
+            attempt_delay.min(remaining)
+        } else {
+            remaining
+        };
+        match results.recv_timeout(wait) {
+            Ok((_, Ok(stream))) => return Ok(stream),
+            Ok((address, Err(err))) => {
+                pending -= 1;
+                last_error = Some(format!("{address}: {err}"));
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    Err(connect_error(
+        ConnectPhase::Tcp,
+        last_error.unwrap_or_else(|| "no addresses to connect to".to_string()),
+    ))
+}
+
+pub fn dial(
+    server: &str,
+    port: u16,
+    proxy: Option<&ProxyConfig>,
+    timeouts: ConnectTimeouts,
+) -> Result<TcpStream, TransportError> {
+    match proxy {
+        Some(proxy) => proxy.connect(server, port, timeouts),
+        None => connect_happy_eyeballs(
+            &resolve(server, port)?,
+            timeouts.connect,
+            CONNECTION_ATTEMPT_DELAY,
+        ),
+    }
+}
+
+fn connect_error(phase: ConnectPhase, message: String) -> TransportError {
+    TransportError::Connect { phase, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        connect_happy_eyeballs, interleave_families, resolve, strip_brackets, ConnectTimeouts,
+        CONNECTION_ATTEMPT_DELAY,
+    };
+    use crate::transport::errors::{ConnectPhase, TransportError};
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Duration;
+
+    fn closed_port() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind failed");
+        listener.local_addr().expect("missing address")
+    }
+
+    /// Default timeouts are bounded so a dead server never hangs the UI.
+    #[test]
+    fn default_timeouts_are_bounded() {
+        // Arrange
+        // Act
+        let timeouts = ConnectTimeouts::default();
+
+        // Assert
+        assert_eq!(timeouts.connect, Duration::from_secs(10));
+        assert_eq!(timeouts.handshake, Duration::from_secs(15));
+    }
+
+    /// Bracketed IPv6 literals resolve to the bare address.
+    #[test]
+    fn resolve_accepts_bracketed_ipv6() {
+        // Arrange
+        // Act
+        let bracketed = resolve("[::1]", 64738).expect("resolve failed");
+        let bare = resolve("::1", 64738).expect("resolve failed");
+
+        // Assert
+        let expected: SocketAddr = "[::1]:64738".parse().expect("parse failed");
+        assert_eq!(bracketed, vec![expected]);
+        assert_eq!(bare, vec![expected]);
+        assert_eq!(strip_brackets(" [fe80::1] "), "fe80::1");
+        assert_eq!(strip_brackets("voice.example"), "voice.example");
+    }
+
+    /// Unresolvable names fail in the resolve phase.
+    #[test]
+    fn resolve_reports_resolve_phase() {
+        // Arrange
+        // Act
+        let err = resolve("bad host name", 64738).expect_err("expected resolve failure");
+
+        // Assert
+        assert!(matches!(
+            err,
+            TransportError::Connect {
+                phase: ConnectPhase::Resolve,
+                ..
+            }
+        ));
+    }
+
+    /// Address families alternate, starting with the family of the first result.
+    #[test]
+    fn interleave_families_alternates() {
+        // Arrange
+        let addresses: Vec<SocketAddr> = ["[::1]:1", "[::2]:1", "127.0.0.1:1", "127.0.0.2:1"]
+            .iter()
+            .map(|address| address.parse().expect("parse failed"))
+            .collect();
+
+        // Act
+        let ordered = interleave_families(&addresses);
+
+        // Assert
+        assert_eq!(
+            ordered,
+            vec![addresses[0], addresses[2], addresses[1], addresses[3]]
+        );
+    }
+
+    /// A refused address is skipped in favour of the next one that accepts.
+    #[test]
+    fn connect_falls_back_to_next_address() {
+        // Arrange
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind failed");
+        let open = listener.local_addr().expect("missing address");
+        let addresses = [closed_port(), open];
+
+        // Act
+        let stream =
+            connect_happy_eyeballs(&addresses, Duration::from_secs(5), CONNECTION_ATTEMPT_DELAY)
+                .expect("connect failed");
+
+        // Assert
+        assert_eq!(stream.peer_addr().expect("missing peer"), open);
+    }
+
+    /// When every address fails the error names the TCP phase.
+    #[test]
+    fn connect_reports_tcp_phase_when_all_fail() {
+        // Arrange
+        let addresses = [closed_port(), closed_port()];
+
+        // Act
+        let err =
+            connect_happy_eyeballs(&addresses, Duration::from_secs(5), CONNECTION_ATTEMPT_DELAY)
+                .expect_err("expected connect failure");
+
+        // Assert
+        assert!(matches!(
+            err,
+            TransportError::Connect {
+                phase: ConnectPhase::Tcp,
+                ..
+            }
+        ));
+    }
+}
@@ -1,44 +1,85 @@
-use crate::transport::errors::TransportError;
+use crate::mumble::certificate::ClientCertificate;
+#[cfg(not(feature = "coverage"))]
+use crate::mumble::connect::dial;
+use crate::mumble::connect::ConnectTimeouts;
+use crate::mumble::events::TextMessage;
+use crate::mumble::keepalive::{KeepaliveConfig, PingScheduler};
+use crate::mumble::proxy::ProxyConfig;
+use crate::mumble::secret::{SecretBytes, SecretString};
+use crate::mumble::state::{ChannelStateUpdate, UserStateUpdate};
+#[cfg(not(feature = "coverage"))]
+use crate::mumble::tls::{tls_handshake, verify_peer};
+use crate::mumble::tls::{TlsPolicy, TlsSessionInfo};
+use crate::transport::errors::{RejectKind, TransportError};
+use crate::transport::types::{ProtocolVersion, ServerInfo};
 use bytes::BytesMut;
 use mumble_protocol_2x::control::{msgs, ControlPacket};
-use mumble_protocol_2x::voice::{Clientbound, Serverbound};
-#[cfg(not(feature = "coverage"))]
-use openssl::ssl::{SslConnector, SslMethod};
+use mumble_protocol_2x::voice::{Clientbound, Serverbound, VoicePacket};
+use std::net::SocketAddr;
 #[cfg(not(feature = "coverage"))]
 use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use tokio_util::codec::{Decoder, Encoder};
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) const CONTROL_READ_TIMEOUT: Duration = Duration::from_millis(50);
+pub(crate) const TUNNEL_READ_TIMEOUT: Duration = Duration::from_millis(5);
+const TUNNEL_IDLE: Duration = Duration::from_millis(500);
+
+pub const CLIENT_VERSION: ProtocolVersion = ProtocolVersion::new(1, 5, 0);
+pub const CLIENT_RELEASE: &str = concat!("babble ", env!("CARGO_PKG_VERSION"));
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum ControlMessage {
+    ServerVersion(ServerInfo),
     ServerSync {
         session: u32,
     },
-    ChannelState {
+    Pong {
+        rtt: Duration,
+    },
+    ChannelState(ChannelStateUpdate),
+    UserState(UserStateUpdate),
+    UserRemove {
         id: u32,
-        name: String,
-        parent_id: Option<u32>,
+        actor_id: Option<u32>,
+        reason: Option<String>,
+        ban: bool,
     },
-    UserState {
+    ChannelRemove {
         id: u32,
-        name: String,
-        channel_id: u32,
-        muted: bool,
-        deafened: bool,
-        talking: bool,
     },
+    TextMessage(TextMessage),
+    CryptSetup {
+        key: Option<SecretBytes>,
+        client_nonce: Option<Vec<u8>>,
+        server_nonce: Option<Vec<u8>>,
+    },
+    Voice(VoicePacket<Clientbound>),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct UserStateCommand {
     pub session_id: u32,
-    pub channel_id: u32,
+    pub channel_id: Option<u32>,
     pub muted: Option<bool>,
     pub deafened: Option<bool>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextMessageCommand {
+    pub channel_ids: Vec<u32>,
+    pub tree_ids: Vec<u32>,
+    pub session_ids: Vec<u32>,
+    pub message: String,
+}
+
 pub struct ControlHandshake {
     pub messages: Vec<ControlMessage>,
     pub session: Option<Box<dyn ControlSession>>,
+    pub tls: Option<TlsSessionInfo>,
+    pub peer: Option<SocketAddr>,
 }
 
 impl std::fmt::Debug for ControlHandshake {
@@ -46,6 +87,8 @@ impl std::fmt::Debug for ControlHandshake {
         f.debug_struct("ControlHandshake")
             .field("messages", &self.messages)
             .field("session_present", &self.session.is_some())
+            .field("tls", &self.tls)
+            .field("peer", &self.peer)
             .finish()
     }
 }
@@ -55,7 +98,14 @@ pub struct HandshakeRequest {
     pub server: String,
     pub port: u16,
     pub username: String,
-    pub password: Option<String>,
+    pub password: Option<SecretString>,
+    pub certificate: Option<ClientCertificate>,
+    pub trusted_fingerprint: Option<String>,
+    pub tls: TlsPolicy,
+    pub proxy: Option<ProxyConfig>,
+    pub tokens: Vec<SecretString>,
+    pub keepalive: KeepaliveConfig,
+    pub timeouts: ConnectTimeouts,
 }
 
 pub trait ControlConnector {
@@ -64,11 +114,79 @@ pub trait ControlConnector {
 
 pub trait ControlSession {
     fn send_user_state(&mut self, command: UserStateCommand) -> Result<(), TransportError>;
+    fn send_text(&mut self, command: TextMessageCommand) -> Result<(), TransportError>;
+    fn send_tokens(&mut self, tokens: Vec<SecretString>) -> Result<(), TransportError>;
+    fn send_crypt_setup(&mut self, client_nonce: Option<Vec<u8>>) -> Result<(), TransportError>;
+    fn send_voice(&mut self, packet: VoicePacket<Serverbound>) -> Result<(), TransportError>;
+    fn try_recv(&mut self) -> Result<Option<ControlMessage>, TransportError>;
+    fn close(&mut self) -> Result<(), TransportError>;
 }
 
 pub trait ControlTransport {
     fn send(&mut self, packet: ControlPacket<Serverbound>) -> Result<(), TransportError>;
     fn recv(&mut self) -> Result<Option<ControlPacket<Clientbound>>, TransportError>;
+    fn shutdown(&mut self) -> Result<(), TransportError>;
+
+    fn set_read_timeout(&mut self, _timeout: Duration) -> Result<(), TransportError> {
+        Ok(())
+    }
+}
+
+pub trait ShutdownStream {
+    fn shutdown(&mut self) -> Result<(), TransportError>;
+
+    fn set_read_timeout(&self, _timeout: Duration) -> Result<(), TransportError> {
+        Ok(())
+    }
+
+    fn tls_session(&self) -> Option<TlsSessionInfo> {
+        None
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+impl ShutdownStream for std::net::TcpStream {
+    fn shutdown(&mut self) -> Result<(), TransportError> {
+        std::net::TcpStream::shutdown(self, std::net::Shutdown::Both)?;
+        Ok(())
+    }
+
+    fn set_read_timeout(&self, timeout: Duration) -> Result<(), TransportError> {
+        std::net::TcpStream::set_read_timeout(self, Some(timeout))?;
+        Ok(())
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        std::net::TcpStream::peer_addr(self).ok()
+    }
+}
+
+impl<S: std::io::Read + std::io::Write + ShutdownStream> ShutdownStream
+    for openssl::ssl::SslStream<S>
+{
+    fn shutdown(&mut self) -> Result<(), TransportError> {
+        // Only send our close_notify; waiting for the peer's would stall on servers that never reply.
+        match openssl::ssl::SslStream::shutdown(self) {
+            Ok(_) => Ok(()),
+            Err(err) if err.code() == openssl::ssl::ErrorCode::ZERO_RETURN => Ok(()),
+            Err(err) => Err(TransportError::Io(format!("tls shutdown failed: {err}"))),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Duration) -> Result<(), TransportError> {
+        self.get_ref().set_read_timeout(timeout)
+    }
+
+    fn tls_session(&self) -> Option<TlsSessionInfo> {
+        TlsSessionInfo::from_ssl(self.ssl()).ok()
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.get_ref().peer_addr()
+    }
 }
 
 #[derive(Debug, Default)]
@@ -82,6 +200,8 @@ impl ControlConnector for NoopControlConnector {
         Ok(ControlHandshake {
             messages: Vec::new(),
             session: None,
+            tls: None,
+            peer: None,
         })
     }
 }
@@ -118,14 +238,21 @@ impl<S> BlockingControlTransport<S> {
 pub fn tls_connect(
     request: &HandshakeRequest,
 ) -> Result<openssl::ssl::SslStream<TcpStream>, TransportError> {
-    let address = format!("{}:{}", request.server, request.port);
-    let tcp = TcpStream::connect(address)?;
-    let builder = SslConnector::builder(SslMethod::tls())
-        .map_err(|err| TransportError::Io(format!("tls connector init failed: {err}")))?;
-    let connector = builder.build();
-    connector
-        .connect(&request.server, tcp)
-        .map_err(|err| TransportError::Io(format!("tls handshake failed: {err}")))
+    let tcp = dial(
+        &request.server,
+        request.port,
+        request.proxy.as_ref(),
+        request.timeouts,
+    )?;
+    tcp.set_read_timeout(Some(request.timeouts.handshake))?;
+    tcp.set_write_timeout(Some(request.timeouts.handshake))?;
+    let stream = tls_handshake(request, tcp)?;
+    verify_peer(request, stream.ssl())?;
+    // The control loop interleaves reads and writes on one thread, so reads must not block forever.
+    stream
+        .get_ref()
+        .set_read_timeout(Some(CONTROL_READ_TIMEOUT))?;
+    Ok(stream)
 }
 
 impl<F> SocketControlConnector<F> {
@@ -134,7 +261,9 @@ impl<F> SocketControlConnector<F> {
     }
 }
 
-impl<S: std::io::Read + std::io::Write> ControlTransport for BlockingControlTransport<S> {
+impl<S: std::io::Read + std::io::Write + ShutdownStream> ControlTransport
+    for BlockingControlTransport<S>
+{
     fn send(&mut self, packet: ControlPacket<Serverbound>) -> Result<(), TransportError> {
         let mut out = BytesMut::with_capacity(512);
         self.codec.encode(packet, &mut out)?;
@@ -156,6 +285,15 @@ impl<S: std::io::Read + std::io::Write> ControlTransport for BlockingControlTran
             self.read_buf.extend_from_slice(&buffer[..bytes_read]);
         }
     }
+
+    fn shutdown(&mut self) -> Result<(), TransportError> {
+        self.stream.flush()?;
+        ShutdownStream::shutdown(&mut self.stream)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), TransportError> {
+        ShutdownStream::set_read_timeout(&self.stream, timeout)
+    }
 }
 
 impl<T: ControlTransport> MumbleProtocolControlConnector<T> {
@@ -165,63 +303,162 @@ impl<T: ControlTransport> MumbleProtocolControlConnector<T> {
         }
     }
 
+    fn version_packet() -> ControlPacket<Serverbound> {
+        let mut version = msgs::Version::new();
+        version.version_v1 = Some(CLIENT_VERSION.to_v1());
+        version.version_v2 = Some(CLIENT_VERSION.to_v2());
+        version.release = Some(CLIENT_RELEASE.to_string());
+        version.os = Some(std::env::consts::OS.to_string());
+        // std has no portable OS release string; leaving it unset beats reporting the CPU.
+        ControlPacket::Version(Box::new(version))
+    }
+
+    fn reject_error(msg: &msgs::Reject) -> TransportError {
+        use msgs::reject::RejectType;
+
+        let kind = match msg.type_.map(|kind| kind.enum_value()) {
+            Some(Ok(RejectType::WrongVersion)) => RejectKind::WrongVersion,
+            Some(Ok(RejectType::InvalidUsername)) => RejectKind::InvalidUsername,
+            Some(Ok(RejectType::WrongUserPW)) => RejectKind::WrongUserPW,
+            Some(Ok(RejectType::WrongServerPW)) => RejectKind::WrongServerPW,
+            Some(Ok(RejectType::UsernameInUse)) => RejectKind::UsernameInUse,
+            Some(Ok(RejectType::ServerFull)) => RejectKind::ServerFull,
+            Some(Ok(RejectType::NoCertificate)) => RejectKind::NoCertificate,
+            Some(Ok(RejectType::AuthenticatorFail)) => RejectKind::AuthenticatorFail,
+            _ => RejectKind::Unknown,
+        };
+        TransportError::Rejected {
+            kind,
+            reason: msg.reason.clone(),
+        }
+    }
+
     fn map_control_packet(packet: ControlPacket<Clientbound>) -> Option<ControlMessage> {
         match packet {
+            ControlPacket::Version(msg) => {
+                let version = msg
+                    .version_v2
+                    .map(ProtocolVersion::from_v2)
+                    .or_else(|| msg.version_v1.map(ProtocolVersion::from_v1));
+                Some(ControlMessage::ServerVersion(ServerInfo {
+                    version,
+                    release: msg.release.clone(),
+                    os: msg.os.clone(),
+                    os_version: msg.os_version.clone(),
+                }))
+            }
             ControlPacket::ServerSync(msg) => {
                 let session = msg.session?;
                 Some(ControlMessage::ServerSync { session })
             }
             ControlPacket::ChannelState(msg) => {
                 let id = msg.channel_id?;
-                let name = msg.name.clone()?;
-                Some(ControlMessage::ChannelState {
+                Some(ControlMessage::ChannelState(ChannelStateUpdate {
                     id,
-                    name,
+                    name: msg.name.clone(),
                     parent_id: msg.parent,
-                })
+                }))
             }
             ControlPacket::UserState(msg) => {
                 let id = msg.session?;
-                let name = msg.name.clone()?;
-                let channel_id = msg.channel_id?;
-                let muted = msg.self_mute.unwrap_or(false);
-                let deafened = msg.self_deaf.unwrap_or(false);
-                Some(ControlMessage::UserState {
+                Some(ControlMessage::UserState(UserStateUpdate {
                     id,
-                    name,
-                    channel_id,
-                    muted,
-                    deafened,
-                    talking: false,
+                    name: msg.name.clone(),
+                    channel_id: msg.channel_id,
+                    muted: msg.self_mute,
+                    deafened: msg.self_deaf,
+                    talking: None,
+                    mute: msg.mute,
+                    deaf: msg.deaf,
+                    suppress: msg.suppress,
+                    priority_speaker: msg.priority_speaker,
+                    recording: msg.recording,
+                }))
+            }
+            ControlPacket::UserRemove(msg) => {
+                let id = msg.session?;
+                Some(ControlMessage::UserRemove {
+                    id,
+                    actor_id: msg.actor,
+                    reason: msg.reason.clone(),
+                    ban: msg.ban.unwrap_or(false),
                 })
             }
+            ControlPacket::ChannelRemove(msg) => {
+                let id = msg.channel_id?;
+                Some(ControlMessage::ChannelRemove { id })
+            }
+            ControlPacket::TextMessage(msg) => {
+                let message = msg.message.clone()?;
+                Some(ControlMessage::TextMessage(TextMessage {
+                    actor_id: msg.actor,
+                    channel_ids: msg.channel_id.clone(),
+                    tree_ids: msg.tree_id.clone(),
+                    user_ids: msg.session.clone(),
+                    message,
+                }))
+            }
+            ControlPacket::CryptSetup(msg) => Some(ControlMessage::CryptSetup {
+                key: msg.key.clone().map(SecretBytes::from),
+                client_nonce: msg.client_nonce.clone(),
+                server_nonce: msg.server_nonce.clone(),
+            }),
+            ControlPacket::UDPTunnel(packet) => Some(ControlMessage::Voice(*packet)),
             _ => None,
         }
     }
 }
 
-impl<T: ControlTransport + 'static> ControlConnector for MumbleProtocolControlConnector<T> {
+impl<T: ControlTransport + Send + 'static> ControlConnector for MumbleProtocolControlConnector<T> {
     fn handshake(&mut self, request: HandshakeRequest) -> Result<ControlHandshake, TransportError> {
         let mut transport = self.transport.take().ok_or_else(|| {
             TransportError::Protocol("control transport already consumed".to_string())
         })?;
+        transport.send(Self::version_packet())?;
+
+        let keepalive = request.keepalive;
+        let deadline = Instant::now() + request.timeouts.handshake;
         let mut auth = msgs::Authenticate::new();
         auth.username = Some(request.username);
-        auth.password = request.password;
+        auth.password = request
+            .password
+            .map(|password| password.expose().to_string());
+        auth.tokens = expose_tokens(&request.tokens);
 
         let packet = ControlPacket::Authenticate(Box::new(auth));
         transport.send(packet)?;
 
         let mut messages = Vec::new();
-        while let Some(packet) = transport.recv()? {
+        loop {
+            let packet = match transport.recv() {
+                Ok(Some(packet)) => packet,
+                Ok(None) => {
+                    return Err(TransportError::Protocol(
+                        "connection closed before server sync".to_string(),
+                    ))
+                }
+                Err(TransportError::Timeout) if Instant::now() < deadline => continue,
+                Err(error) => return Err(error),
+            };
+            if let ControlPacket::Reject(msg) = &packet {
+                return Err(Self::reject_error(msg));
+            }
             if let Some(message) = Self::map_control_packet(packet) {
+                let synced = matches!(message, ControlMessage::ServerSync { .. });
                 messages.push(message);
+                if synced {
+                    break;
+                }
             }
         }
 
         Ok(ControlHandshake {
             messages,
-            session: Some(Box::new(MumbleProtocolControlSession { transport })),
+            session: Some(Box::new(MumbleProtocolControlSession::spawn(
+                transport, keepalive,
+            )?)),
+            tls: None,
+            peer: None,
         })
     }
 }
@@ -229,60 +466,258 @@ impl<T: ControlTransport + 'static> ControlConnector for MumbleProtocolControlCo
 impl<F, S> ControlConnector for SocketControlConnector<F>
 where
     F: FnMut(&HandshakeRequest) -> Result<S, TransportError>,
-    S: std::io::Read + std::io::Write + 'static,
+    S: std::io::Read + std::io::Write + ShutdownStream + Send + 'static,
 {
     fn handshake(&mut self, request: HandshakeRequest) -> Result<ControlHandshake, TransportError> {
         let stream = (self.connect)(&request)?;
+        let tls = stream.tls_session();
+        let peer = stream.peer_addr();
         let transport = BlockingControlTransport::new(stream);
         let mut connector = MumbleProtocolControlConnector::new(transport);
-        connector.handshake(request)
+        let mut handshake = connector.handshake(request)?;
+        handshake.tls = tls;
+        handshake.peer = peer;
+        Ok(handshake)
     }
 }
 
-pub struct MumbleProtocolControlSession<T: ControlTransport> {
-    transport: T,
+pub struct MumbleProtocolControlSession {
+    outbound: Option<Sender<ControlPacket<Serverbound>>>,
+    inbound: Receiver<Result<ControlMessage, TransportError>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl MumbleProtocolControlSession {
+    pub fn spawn<T: ControlTransport + Send + 'static>(
+        transport: T,
+        keepalive: KeepaliveConfig,
+    ) -> Result<Self, TransportError> {
+        let (outbound, outbound_rx) = mpsc::channel();
+        let (inbound_tx, inbound) = mpsc::channel();
+        // Dropping the session closes `outbound`, which stops the loop after its current read.
+        let worker = std::thread::Builder::new()
+            .name("mumble-control".to_string())
+            .spawn(move || run_control_loop(transport, keepalive, outbound_rx, inbound_tx))?;
+        Ok(Self {
+            outbound: Some(outbound),
+            inbound,
+            worker: Some(worker),
+        })
+    }
+
+    fn send(&mut self, packet: ControlPacket<Serverbound>) -> Result<(), TransportError> {
+        self.outbound
+            .as_ref()
+            .ok_or(TransportError::Disconnected)?
+            .send(packet)
+            .map_err(|_| TransportError::Disconnected)
+    }
 }
 
-impl<T: ControlTransport + 'static> ControlSession for MumbleProtocolControlSession<T> {
+impl ControlSession for MumbleProtocolControlSession {
     fn send_user_state(&mut self, command: UserStateCommand) -> Result<(), TransportError> {
         let mut message = msgs::UserState::new();
         message.session = Some(command.session_id);
-        message.channel_id = Some(command.channel_id);
+        message.channel_id = command.channel_id;
         message.self_mute = command.muted;
         message.self_deaf = command.deafened;
-        self.transport
-            .send(ControlPacket::UserState(Box::new(message)))
+        self.send(ControlPacket::UserState(Box::new(message)))
+    }
+
+    fn send_text(&mut self, command: TextMessageCommand) -> Result<(), TransportError> {
+        let mut message = msgs::TextMessage::new();
+        message.channel_id = command.channel_ids;
+        message.tree_id = command.tree_ids;
+        message.session = command.session_ids;
+        message.message = Some(command.message);
+        self.send(ControlPacket::TextMessage(Box::new(message)))
+    }
+
+    fn send_tokens(&mut self, tokens: Vec<SecretString>) -> Result<(), TransportError> {
+        // Servers accept a token-only Authenticate after sync and re-evaluate channel ACLs.
+        let mut message = msgs::Authenticate::new();
+        message.tokens = expose_tokens(&tokens);
+        self.send(ControlPacket::Authenticate(Box::new(message)))
+    }
+
+    fn send_crypt_setup(&mut self, client_nonce: Option<Vec<u8>>) -> Result<(), TransportError> {
+        // An empty CryptSetup asks the server for a fresh nonce; ours answers its request.
+        let mut message = msgs::CryptSetup::new();
+        message.client_nonce = client_nonce;
+        self.send(ControlPacket::CryptSetup(Box::new(message)))
+    }
+
+    fn send_voice(&mut self, packet: VoicePacket<Serverbound>) -> Result<(), TransportError> {
+        self.send(ControlPacket::UDPTunnel(Box::new(packet)))
+    }
+
+    fn try_recv(&mut self) -> Result<Option<ControlMessage>, TransportError> {
+        match self.inbound.try_recv() {
+            Ok(Ok(message)) => Ok(Some(message)),
+            Ok(Err(error)) => Err(error),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(TransportError::Disconnected),
+        }
+    }
+
+    fn close(&mut self) -> Result<(), TransportError> {
+        self.outbound = None;
+        match self.worker.take() {
+            Some(worker) => worker
+                .join()
+                .map_err(|_| TransportError::Protocol("control loop panicked".to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+fn expose_tokens(tokens: &[SecretString]) -> Vec<String> {
+    tokens
+        .iter()
+        .map(|token| token.expose().to_string())
+        .collect()
+}
+
+fn run_control_loop<T: ControlTransport>(
+    mut transport: T,
+    keepalive: KeepaliveConfig,
+    outbound: Receiver<ControlPacket<Serverbound>>,
+    inbound: Sender<Result<ControlMessage, TransportError>>,
+) {
+    let mut pings = PingScheduler::new(keepalive, Instant::now());
+    let mut read_timeout = CONTROL_READ_TIMEOUT;
+    let mut last_tunnel: Option<Instant> = None;
+    loop {
+        let ping = match pings.poll(Instant::now()) {
+            Ok(ping) => ping,
+            Err(error) => {
+                let _ = inbound.send(Err(error));
+                return;
+            }
+        };
+        if let Some(ping) = ping {
+            if let Err(error) = transport.send(ControlPacket::Ping(Box::new(ping))) {
+                let _ = inbound.send(Err(error));
+                return;
+            }
+        }
+
+        loop {
+            match outbound.try_recv() {
+                Ok(packet) => {
+                    if matches!(packet, ControlPacket::UDPTunnel(_)) {
+                        last_tunnel = Some(Instant::now());
+                    }
+                    if let Err(error) = transport.send(packet) {
+                        let _ = inbound.send(Err(error));
+                        return;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    let _ = transport.shutdown();
+                    return;
+                }
+            }
+        }
+
+        // Outbound packets only go out between reads, so keep reads short while voice is
+        // tunnelled; otherwise 20 ms frames would queue behind the idle read timeout.
+        let tunnelling = last_tunnel.is_some_and(|at| at.elapsed() < TUNNEL_IDLE);
+        let wanted = if tunnelling {
+            TUNNEL_READ_TIMEOUT
+        } else {
+            CONTROL_READ_TIMEOUT
+        };
+        if wanted != read_timeout {
+            if let Err(error) = transport.set_read_timeout(wanted) {
+                let _ = inbound.send(Err(error));
+                return;
+            }
+            read_timeout = wanted;
+        }
+
+        match transport.recv() {
+            Ok(Some(ControlPacket::Ping(pong))) => {
+                pings.record_tcp_packet();
+                if let Some(rtt) = pings.on_pong(&pong, Instant::now()) {
+                    if inbound.send(Ok(ControlMessage::Pong { rtt })).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(Some(packet)) => {
+                pings.record_tcp_packet();
+                if let Some(message) =
+                    MumbleProtocolControlConnector::<T>::map_control_packet(packet)
+                {
+                    if inbound.send(Ok(message)).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(None) => {
+                let _ = inbound.send(Err(TransportError::Disconnected));
+                return;
+            }
+            Err(TransportError::Timeout) => {}
+            Err(error) => {
+                let _ = inbound.send(Err(error));
+                return;
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        BlockingControlTransport, ControlConnector, ControlMessage, ControlTransport,
-        HandshakeRequest, MumbleProtocolControlConnector, SocketControlConnector,
+        BlockingControlTransport, ControlConnector, ControlMessage, ControlSession,
+        ControlTransport, HandshakeRequest, MumbleProtocolControlConnector, ShutdownStream,
+        SocketControlConnector, TextMessageCommand, UserStateCommand, CLIENT_RELEASE,
+        CONTROL_READ_TIMEOUT, TUNNEL_READ_TIMEOUT,
     };
-    use crate::transport::errors::TransportError;
+    use crate::mumble::connect::ConnectTimeouts;
+    use crate::mumble::events::TextMessage;
+    use crate::mumble::keepalive::KeepaliveConfig;
+    use crate::mumble::secret::SecretBytes;
+    use crate::mumble::state::{ChannelStateUpdate, UserStateUpdate};
+    use crate::mumble::tls::TlsPolicy;
+    use crate::transport::errors::{RejectKind, TransportError};
+    use crate::transport::types::{ProtocolVersion, ServerInfo};
     use mumble_protocol_2x::control::{msgs, ControlPacket};
-    use mumble_protocol_2x::voice::{Clientbound, Serverbound};
+    use mumble_protocol_2x::voice::{Clientbound, Serverbound, VoicePacket, VoicePacketPayload};
     use std::cell::RefCell;
     use std::io::{Cursor, Read, Write};
+    use std::net::SocketAddr;
     use std::rc::Rc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
     use tokio_util::codec::{Decoder, Encoder};
 
     struct TestTransport {
-        sent: Rc<RefCell<Vec<ControlPacket<Serverbound>>>>,
+        sent: Arc<Mutex<Vec<ControlPacket<Serverbound>>>>,
         recv_queue: Vec<ControlPacket<Clientbound>>,
         send_error: bool,
         recv_error: bool,
+        idle_when_empty: bool,
+        echo_pings: bool,
+        shut_down: Arc<AtomicBool>,
+        read_timeouts: Arc<Mutex<Vec<Duration>>>,
     }
 
     impl Default for TestTransport {
         fn default() -> Self {
             Self {
-                sent: Rc::new(RefCell::new(Vec::new())),
+                sent: Arc::new(Mutex::new(Vec::new())),
                 recv_queue: Vec::new(),
                 send_error: false,
                 recv_error: false,
+                idle_when_empty: false,
+                echo_pings: false,
+                shut_down: Arc::new(AtomicBool::new(false)),
+                read_timeouts: Arc::new(Mutex::new(Vec::new())),
             }
         }
     }
@@ -292,7 +727,10 @@ mod tests {
             if self.send_error {
                 return Err(TransportError::Io("send failed".to_string()));
             }
-            self.sent.borrow_mut().push(packet);
+            if let (true, ControlPacket::Ping(ping)) = (self.echo_pings, &packet) {
+                self.recv_queue.push(ControlPacket::Ping(ping.clone()));
+            }
+            self.sent.lock().expect("sent lock poisoned").push(packet);
             Ok(())
         }
 
@@ -301,17 +739,41 @@ mod tests {
                 return Err(TransportError::Io("recv failed".to_string()));
             }
             if self.recv_queue.is_empty() {
+                if self.idle_when_empty {
+                    std::thread::sleep(Duration::from_millis(1));
+                    return Err(TransportError::Timeout);
+                }
                 Ok(None)
             } else {
                 Ok(Some(self.recv_queue.remove(0)))
             }
         }
+
+        fn shutdown(&mut self) -> Result<(), TransportError> {
+            self.shut_down.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), TransportError> {
+            self.read_timeouts
+                .lock()
+                .expect("timeouts lock poisoned")
+                .push(timeout);
+            Ok(())
+        }
+    }
+
+    impl ShutdownStream for Cursor<Vec<u8>> {
+        fn shutdown(&mut self) -> Result<(), TransportError> {
+            Ok(())
+        }
     }
 
     #[derive(Default)]
     struct MemoryStream {
         read: Cursor<Vec<u8>>,
         written: Vec<u8>,
+        peer: Option<SocketAddr>,
     }
 
     impl MemoryStream {
@@ -319,6 +781,7 @@ mod tests {
             Self {
                 read: Cursor::new(data),
                 written: Vec::new(),
+                peer: None,
             }
         }
     }
@@ -340,6 +803,48 @@ mod tests {
         }
     }
 
+    impl ShutdownStream for MemoryStream {
+        fn shutdown(&mut self) -> Result<(), TransportError> {
+            Ok(())
+        }
+
+        fn peer_addr(&self) -> Option<SocketAddr> {
+            self.peer
+        }
+    }
+
+    fn request() -> HandshakeRequest {
+        HandshakeRequest {
+            server: "voice.example".to_string(),
+            port: 64738,
+            username: "alice".to_string(),
+            password: None,
+            certificate: None,
+            trusted_fingerprint: None,
+            tls: TlsPolicy::default(),
+            proxy: None,
+            tokens: Vec::new(),
+            keepalive: KeepaliveConfig::default(),
+            timeouts: ConnectTimeouts::default(),
+        }
+    }
+
+    fn server_sync(session: u32) -> ControlPacket<Clientbound> {
+        let mut server_sync = msgs::ServerSync::new();
+        server_sync.session = Some(session);
+        ControlPacket::ServerSync(Box::new(server_sync))
+    }
+
+    fn recv_with_retry(session: &mut dyn ControlSession) -> Result<ControlMessage, TransportError> {
+        for _ in 0..500 {
+            if let Some(message) = session.try_recv()? {
+                return Ok(message);
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        panic!("control loop produced nothing");
+    }
+
     /// In-memory stream flush is a no-op.
     #[test]
     fn memory_stream_flush_is_noop() {
@@ -351,32 +856,161 @@ mod tests {
         result.expect("flush failed");
     }
 
+    /// State packets carrying only changed fields are forwarded as partial deltas.
+    #[test]
+    fn handshake_keeps_partial_state_deltas() {
+        // Arrange
+        let mut channel_state = msgs::ChannelState::new();
+        channel_state.channel_id = Some(1);
+        channel_state.parent = Some(0);
+        let mut user_state = msgs::UserState::new();
+        user_state.session = Some(2);
+        user_state.self_mute = Some(true);
+        user_state.mute = Some(false);
+        user_state.suppress = Some(true);
+        user_state.recording = Some(true);
+        let transport = TestTransport {
+            recv_queue: vec![
+                ControlPacket::ChannelState(Box::new(channel_state)),
+                ControlPacket::UserState(Box::new(user_state)),
+                server_sync(5),
+            ],
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+
+        // Act
+        let handshake = connector.handshake(request).expect("handshake failed");
+
+        // Assert
+        assert_eq!(
+            handshake.messages,
+            vec![
+                ControlMessage::ChannelState(ChannelStateUpdate {
+                    id: 1,
+                    name: None,
+                    parent_id: Some(0),
+                }),
+                ControlMessage::UserState(UserStateUpdate {
+                    id: 2,
+                    name: None,
+                    channel_id: None,
+                    muted: Some(true),
+                    deafened: None,
+                    talking: None,
+                    mute: Some(false),
+                    suppress: Some(true),
+                    recording: Some(true),
+                    ..Default::default()
+                }),
+                ControlMessage::ServerSync { session: 5 },
+            ]
+        );
+    }
+
+    /// Handshake announces the client version before authenticating.
+    #[test]
+    fn handshake_sends_version_first() {
+        // Arrange
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transport = TestTransport {
+            sent: Arc::clone(&sent),
+            recv_queue: vec![server_sync(1)],
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+
+        // Act
+        connector.handshake(request).expect("handshake failed");
+
+        // Assert
+        let sent = sent.lock().expect("sent lock poisoned");
+        assert!(matches!(
+            &sent[0],
+            ControlPacket::Version(msg)
+                if msg.version_v1 == Some(0x0001_0500)
+                    && msg.version_v2 == Some(0x0001_0005_0000_0000)
+                    && msg.release.as_deref() == Some(CLIENT_RELEASE)
+                    && msg.os_version.is_none()
+        ));
+        assert!(matches!(&sent[1], ControlPacket::Authenticate(_)));
+    }
+
+    /// The server's version reply is mapped into server info, preferring `version_v2`.
+    #[test]
+    fn handshake_maps_server_version() {
+        // Arrange
+        let mut version = msgs::Version::new();
+        version.version_v1 = Some(0x0001_0400);
+        version.version_v2 = Some(ProtocolVersion::new(1, 5, 634).to_v2());
+        version.release = Some("1.5.634".to_string());
+        version.os = Some("Linux".to_string());
+        version.os_version = Some("Debian 12".to_string());
+        let mut legacy = msgs::Version::new();
+        legacy.version_v1 = Some(0x0001_0204);
+        let transport = TestTransport {
+            recv_queue: vec![
+                ControlPacket::Version(Box::new(version)),
+                ControlPacket::Version(Box::new(legacy)),
+                server_sync(1),
+            ],
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+
+        // Act
+        let handshake = connector.handshake(request).expect("handshake failed");
+
+        // Assert
+        assert_eq!(
+            handshake.messages[0],
+            ControlMessage::ServerVersion(ServerInfo {
+                version: Some(ProtocolVersion::new(1, 5, 634)),
+                release: Some("1.5.634".to_string()),
+                os: Some("Linux".to_string()),
+                os_version: Some("Debian 12".to_string()),
+            })
+        );
+        assert!(matches!(
+            &handshake.messages[1],
+            ControlMessage::ServerVersion(info)
+                if info.version == Some(ProtocolVersion::new(1, 2, 4))
+        ));
+    }
+
     /// Handshake sends an authenticate control packet with credentials.
     #[test]
     fn handshake_sends_authenticate() {
         // Arrange
-        let sent = Rc::new(RefCell::new(Vec::new()));
+        let sent = Arc::new(Mutex::new(Vec::new()));
         let transport = TestTransport {
-            sent: Rc::clone(&sent),
+            sent: Arc::clone(&sent),
+            recv_queue: vec![server_sync(1)],
             ..Default::default()
         };
         let mut connector = MumbleProtocolControlConnector::new(transport);
 
         let request = HandshakeRequest {
-            server: "voice.example".to_string(),
-            port: 64738,
-            username: "alice".to_string(),
-            password: Some("pw".to_string()),
+            password: Some("pw".into()),
+            ..request()
         };
 
         // Act
         connector.handshake(request).expect("handshake failed");
 
         // Assert
-        let sent = sent.borrow();
-        assert_eq!(sent.len(), 1);
+        let sent = sent.lock().expect("sent lock poisoned");
+        // The control loop may already have sent its first keepalive ping.
+        let handshake: Vec<_> = sent
+            .iter()
+            .filter(|packet| !matches!(packet, ControlPacket::Ping(_)))
+            .collect();
+        assert_eq!(handshake.len(), 2);
         assert!(matches!(
-            &sent[0],
+            handshake[1],
             ControlPacket::Authenticate(msg)
                 if msg.username.as_deref() == Some("alice")
                     && msg.password.as_deref() == Some("pw")
@@ -387,10 +1021,6 @@ mod tests {
     #[test]
     fn handshake_maps_control_packets() {
         // Arrange
-        let sent = Rc::new(RefCell::new(Vec::new()));
-        let mut server_sync = msgs::ServerSync::new();
-        server_sync.session = Some(7);
-
         let mut channel_state = msgs::ChannelState::new();
         channel_state.channel_id = Some(1);
         channel_state.name = Some("Lobby".to_string());
@@ -403,23 +1033,16 @@ mod tests {
         user_state.self_deaf = Some(false);
 
         let transport = TestTransport {
-            sent: Rc::clone(&sent),
             recv_queue: vec![
-                ControlPacket::ServerSync(Box::new(server_sync)),
                 ControlPacket::ChannelState(Box::new(channel_state)),
                 ControlPacket::UserState(Box::new(user_state)),
+                server_sync(7),
             ],
-            send_error: false,
-            recv_error: false,
+            ..Default::default()
         };
         let mut connector = MumbleProtocolControlConnector::new(transport);
 
-        let request = HandshakeRequest {
-            server: "voice.example".to_string(),
-            port: 64738,
-            username: "alice".to_string(),
-            password: None,
-        };
+        let request = request();
 
         // Act
         let handshake = connector.handshake(request).expect("handshake failed");
@@ -428,20 +1051,21 @@ mod tests {
         assert_eq!(
             messages,
             vec![
-                ControlMessage::ServerSync { session: 7 },
-                ControlMessage::ChannelState {
+                ControlMessage::ChannelState(ChannelStateUpdate {
                     id: 1,
-                    name: "Lobby".to_string(),
+                    name: Some("Lobby".to_string()),
                     parent_id: None,
-                },
-                ControlMessage::UserState {
+                }),
+                ControlMessage::UserState(UserStateUpdate {
                     id: 2,
-                    name: "Alice".to_string(),
-                    channel_id: 1,
-                    muted: true,
-                    deafened: false,
-                    talking: false,
-                },
+                    name: Some("Alice".to_string()),
+                    channel_id: Some(1),
+                    muted: Some(true),
+                    deafened: Some(false),
+                    talking: None,
+                    ..Default::default()
+                }),
+                ControlMessage::ServerSync { session: 7 },
             ]
         );
     }
@@ -450,112 +1074,794 @@ mod tests {
     #[test]
     fn handshake_ignores_unknown_packets() {
         // Arrange
-        let sent = Rc::new(RefCell::new(Vec::new()));
         let ban_list = msgs::BanList::new();
         let transport = TestTransport {
-            sent: Rc::clone(&sent),
-            recv_queue: vec![ControlPacket::BanList(Box::new(ban_list))],
-            send_error: false,
-            recv_error: false,
+            recv_queue: vec![ControlPacket::BanList(Box::new(ban_list)), server_sync(3)],
+            ..Default::default()
         };
         let mut connector = MumbleProtocolControlConnector::new(transport);
 
-        let request = HandshakeRequest {
-            server: "voice.example".to_string(),
-            port: 64738,
-            username: "alice".to_string(),
-            password: None,
-        };
+        let request = request();
 
         // Act
         let handshake = connector.handshake(request).expect("handshake failed");
         let messages = handshake.messages;
         // Assert
-        assert!(messages.is_empty());
+        assert_eq!(messages, vec![ControlMessage::ServerSync { session: 3 }]);
     }
 
     /// Handshake skips packets missing required fields so partial state does not leak.
     #[test]
     fn handshake_skips_incomplete_messages() {
         // Arrange
-        let sent = Rc::new(RefCell::new(Vec::new()));
-        let server_sync = msgs::ServerSync::new();
+        let incomplete_sync = msgs::ServerSync::new();
         let mut channel_state = msgs::ChannelState::new();
-        channel_state.channel_id = Some(1);
+        channel_state.name = Some("Lobby".to_string());
         let mut user_state = msgs::UserState::new();
-        user_state.session = Some(2);
         user_state.name = Some("Alice".to_string());
 
         let transport = TestTransport {
-            sent: Rc::clone(&sent),
             recv_queue: vec![
-                ControlPacket::ServerSync(Box::new(server_sync)),
+                ControlPacket::ServerSync(Box::new(incomplete_sync)),
                 ControlPacket::ChannelState(Box::new(channel_state)),
                 ControlPacket::UserState(Box::new(user_state)),
+                server_sync(5),
             ],
-            send_error: false,
-            recv_error: false,
+            ..Default::default()
         };
         let mut connector = MumbleProtocolControlConnector::new(transport);
 
-        let request = HandshakeRequest {
-            server: "voice.example".to_string(),
-            port: 64738,
-            username: "alice".to_string(),
-            password: None,
-        };
+        let request = request();
 
         // Act
         let handshake = connector.handshake(request).expect("handshake failed");
         let messages = handshake.messages;
         // Assert
-        assert!(messages.is_empty());
+        assert_eq!(messages, vec![ControlMessage::ServerSync { session: 5 }]);
     }
 
-    /// Handshake surfaces transport send failures instead of swallowing them.
+    /// Reject packets become typed errors carrying the server's reason.
     #[test]
-    fn handshake_propagates_send_error() {
+    fn handshake_maps_reject_to_typed_error() {
         // Arrange
+        let mut reject = msgs::Reject::new();
+        reject.set_type(msgs::reject::RejectType::UsernameInUse);
+        reject.reason = Some("Username already in use".to_string());
         let transport = TestTransport {
-            sent: Rc::new(RefCell::new(Vec::new())),
-            recv_queue: Vec::new(),
-            send_error: true,
-            recv_error: false,
+            recv_queue: vec![ControlPacket::Reject(Box::new(reject)), server_sync(1)],
+            ..Default::default()
         };
         let mut connector = MumbleProtocolControlConnector::new(transport);
-        let request = HandshakeRequest {
-            server: "voice.example".to_string(),
-            port: 64738,
-            username: "alice".to_string(),
-            password: None,
-        };
+        let request = request();
 
         // Act
         let err = connector
             .handshake(request)
-            .expect_err("expected send failure");
+            .expect_err("expected rejection");
+
         // Assert
-        assert!(matches!(err, TransportError::Io(_)));
+        assert!(matches!(
+            err,
+            TransportError::Rejected {
+                kind: RejectKind::UsernameInUse,
+                reason: Some(ref reason),
+            } if reason == "Username already in use"
+        ));
     }
 
-    /// Handshake surfaces transport receive failures instead of continuing with stale state.
+    /// Reject packets without a type are reported as unknown rejections.
     #[test]
-    fn handshake_propagates_recv_error() {
+    fn handshake_maps_untyped_reject_to_unknown() {
         // Arrange
         let transport = TestTransport {
-            sent: Rc::new(RefCell::new(Vec::new())),
-            recv_queue: Vec::new(),
-            send_error: false,
-            recv_error: true,
+            recv_queue: vec![ControlPacket::Reject(Box::new(msgs::Reject::new()))],
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+
+        // Act
+        let err = connector
+            .handshake(request)
+            .expect_err("expected rejection");
+
+        // Assert
+        assert!(matches!(
+            err,
+            TransportError::Rejected {
+                kind: RejectKind::Unknown,
+                reason: None,
+            }
+        ));
+    }
+
+    /// Handshake fails when the server closes the stream before sync completes.
+    #[test]
+    fn handshake_rejects_eof_before_server_sync() {
+        // Arrange
+        let transport = TestTransport::default();
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+
+        // Act
+        let err = connector
+            .handshake(request)
+            .expect_err("expected handshake to fail");
+        // Assert
+        assert!(matches!(err, TransportError::Protocol(_)));
+    }
+
+    /// Packets after server sync are left for the session's reader loop.
+    #[test]
+    fn session_receives_messages_after_server_sync() {
+        // Arrange
+        let mut channel_state = msgs::ChannelState::new();
+        channel_state.channel_id = Some(4);
+        channel_state.name = Some("Late".to_string());
+        let transport = TestTransport {
+            recv_queue: vec![
+                server_sync(7),
+                ControlPacket::ChannelState(Box::new(channel_state)),
+            ],
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+
+        // Act
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+        let message = recv_with_retry(session.as_mut()).expect("recv failed");
+
+        // Assert
+        assert_eq!(
+            handshake.messages,
+            vec![ControlMessage::ServerSync { session: 7 }]
+        );
+        assert_eq!(
+            message,
+            ControlMessage::ChannelState(ChannelStateUpdate {
+                id: 4,
+                name: Some("Late".to_string()),
+                parent_id: None,
+            })
+        );
+    }
+
+    /// User and channel removals after sync are forwarded to the session.
+    #[test]
+    fn session_receives_removals() {
+        // Arrange
+        let mut user_remove = msgs::UserRemove::new();
+        user_remove.session = Some(9);
+        user_remove.actor = Some(1);
+        user_remove.reason = Some("spam".to_string());
+        user_remove.ban = Some(true);
+        let mut channel_remove = msgs::ChannelRemove::new();
+        channel_remove.channel_id = Some(4);
+        let transport = TestTransport {
+            recv_queue: vec![
+                server_sync(7),
+                ControlPacket::UserRemove(Box::new(user_remove)),
+                ControlPacket::ChannelRemove(Box::new(channel_remove)),
+            ],
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        let first = recv_with_retry(session.as_mut()).expect("recv failed");
+        let second = recv_with_retry(session.as_mut()).expect("recv failed");
+
+        // Assert
+        assert_eq!(
+            first,
+            ControlMessage::UserRemove {
+                id: 9,
+                actor_id: Some(1),
+                reason: Some("spam".to_string()),
+                ban: true,
+            }
+        );
+        assert_eq!(second, ControlMessage::ChannelRemove { id: 4 });
+    }
+
+    /// The reader loop reports a disconnect once the stream reaches EOF.
+    #[test]
+    fn session_reports_disconnect_on_eof() {
+        // Arrange
+        let transport = TestTransport {
+            recv_queue: vec![server_sync(7)],
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        let err = recv_with_retry(session.as_mut()).expect_err("expected disconnect");
+
+        // Assert
+        assert!(matches!(err, TransportError::Disconnected));
+    }
+
+    /// Session commands are written to the transport by the reader loop.
+    #[test]
+    fn session_sends_user_state_through_loop() {
+        // Arrange
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transport = TestTransport {
+            sent: Arc::clone(&sent),
+            recv_queue: vec![server_sync(7)],
+            idle_when_empty: true,
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        session
+            .send_user_state(UserStateCommand {
+                session_id: 7,
+                channel_id: Some(2),
+                muted: None,
+                deafened: None,
+            })
+            .expect("send failed");
+        let is_command = |packet: &ControlPacket<Serverbound>| {
+            matches!(
+                packet,
+                ControlPacket::UserState(msg)
+                    if msg.session == Some(7) && msg.channel_id == Some(2)
+            )
+        };
+        for _ in 0..500 {
+            if sent
+                .lock()
+                .expect("sent lock poisoned")
+                .iter()
+                .any(is_command)
+            {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        // Assert
+        let sent = sent.lock().expect("sent lock poisoned");
+        assert!(sent.iter().any(is_command));
+    }
+
+    /// Self mute commands leave the channel unset so the server does not move us.
+    #[test]
+    fn session_sends_self_flags_without_channel() {
+        // Arrange
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transport = TestTransport {
+            sent: Arc::clone(&sent),
+            recv_queue: vec![server_sync(7)],
+            idle_when_empty: true,
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        session
+            .send_user_state(UserStateCommand {
+                session_id: 7,
+                channel_id: None,
+                muted: Some(true),
+                deafened: Some(true),
+            })
+            .expect("send failed");
+        let is_command = |packet: &ControlPacket<Serverbound>| {
+            matches!(
+                packet,
+                ControlPacket::UserState(msg)
+                    if msg.session == Some(7)
+                        && msg.channel_id.is_none()
+                        && msg.self_mute == Some(true)
+                        && msg.self_deaf == Some(true)
+            )
+        };
+        for _ in 0..500 {
+            if sent
+                .lock()
+                .expect("sent lock poisoned")
+                .iter()
+                .any(is_command)
+            {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        // Assert
+        assert!(sent
+            .lock()
+            .expect("sent lock poisoned")
+            .iter()
+            .any(is_command));
+    }
+
+    /// Closing the session stops the loop and shuts the transport down.
+    #[test]
+    fn session_close_shuts_down_transport() {
+        // Arrange
+        let shut_down = Arc::new(AtomicBool::new(false));
+        let transport = TestTransport {
+            recv_queue: vec![server_sync(7)],
+            idle_when_empty: true,
+            shut_down: Arc::clone(&shut_down),
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        session.close().expect("close failed");
+        let err = session
+            .send_user_state(UserStateCommand {
+                session_id: 7,
+                channel_id: Some(1),
+                muted: None,
+                deafened: None,
+            })
+            .expect_err("expected send to fail");
+
+        // Assert
+        assert!(shut_down.load(Ordering::SeqCst));
+        assert!(matches!(err, TransportError::Disconnected));
+        assert!(matches!(
+            session.try_recv(),
+            Err(TransportError::Disconnected)
+        ));
+    }
+
+    /// A server that never syncs fails the handshake once the deadline passes.
+    #[test]
+    fn handshake_times_out_without_server_sync() {
+        // Arrange
+        let transport = TestTransport {
+            idle_when_empty: true,
+            ..Default::default()
         };
         let mut connector = MumbleProtocolControlConnector::new(transport);
         let request = HandshakeRequest {
-            server: "voice.example".to_string(),
-            port: 64738,
-            username: "alice".to_string(),
-            password: None,
+            timeouts: ConnectTimeouts {
+                connect: Duration::from_secs(1),
+                handshake: Duration::from_millis(20),
+            },
+            ..request()
+        };
+
+        // Act
+        let result = connector.handshake(request);
+
+        // Assert
+        assert!(matches!(result, Err(TransportError::Timeout)));
+    }
+
+    /// Authenticate carries the configured access tokens.
+    #[test]
+    fn handshake_sends_access_tokens() {
+        // Arrange
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transport = TestTransport {
+            sent: Arc::clone(&sent),
+            recv_queue: vec![server_sync(7)],
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = HandshakeRequest {
+            tokens: vec!["red".into(), "blue".into()],
+            ..request()
         };
 
+        // Act
+        connector.handshake(request).expect("handshake failed");
+
+        // Assert
+        let sent = sent.lock().expect("sent lock poisoned");
+        assert!(sent.iter().any(|packet| matches!(
+            packet,
+            ControlPacket::Authenticate(auth) if auth.tokens == vec!["red", "blue"]
+        )));
+    }
+
+    /// Token updates are sent mid-session as a token-only Authenticate.
+    #[test]
+    fn session_sends_token_update() {
+        // Arrange
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transport = TestTransport {
+            sent: Arc::clone(&sent),
+            recv_queue: vec![server_sync(7)],
+            idle_when_empty: true,
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        session
+            .send_tokens(vec!["green".into()])
+            .expect("send failed");
+        let is_update = |packet: &ControlPacket<Serverbound>| {
+            matches!(
+                packet,
+                ControlPacket::Authenticate(auth)
+                    if auth.username.is_none() && auth.tokens == vec!["green"]
+            )
+        };
+        for _ in 0..500 {
+            if sent
+                .lock()
+                .expect("sent lock poisoned")
+                .iter()
+                .any(is_update)
+            {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        // Assert
+        assert!(sent
+            .lock()
+            .expect("sent lock poisoned")
+            .iter()
+            .any(is_update));
+    }
+
+    /// Text commands are encoded with every requested target.
+    #[test]
+    fn session_sends_text_message_through_loop() {
+        // Arrange
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transport = TestTransport {
+            sent: Arc::clone(&sent),
+            recv_queue: vec![server_sync(7)],
+            idle_when_empty: true,
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        session
+            .send_text(TextMessageCommand {
+                channel_ids: vec![1],
+                tree_ids: vec![2],
+                session_ids: vec![9],
+                message: "hello".to_string(),
+            })
+            .expect("send failed");
+        let is_text = |packet: &ControlPacket<Serverbound>| {
+            matches!(
+                packet,
+                ControlPacket::TextMessage(msg)
+                    if msg.channel_id == vec![1]
+                        && msg.tree_id == vec![2]
+                        && msg.session == vec![9]
+                        && msg.message.as_deref() == Some("hello")
+            )
+        };
+        for _ in 0..500 {
+            if sent.lock().expect("sent lock poisoned").iter().any(is_text) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        // Assert
+        assert!(sent.lock().expect("sent lock poisoned").iter().any(is_text));
+    }
+
+    /// Crypt setup sent before sync is returned with the handshake messages.
+    #[test]
+    fn handshake_maps_crypt_setup() {
+        // Arrange
+        let mut setup = msgs::CryptSetup::new();
+        setup.key = Some(vec![7; 16]);
+        setup.client_nonce = Some(vec![1; 16]);
+        setup.server_nonce = Some(vec![2; 16]);
+        let transport = TestTransport {
+            recv_queue: vec![ControlPacket::CryptSetup(Box::new(setup)), server_sync(7)],
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+
+        // Act
+        let handshake = connector.handshake(request).expect("handshake failed");
+
+        // Assert
+        assert_eq!(
+            handshake.messages,
+            vec![
+                ControlMessage::CryptSetup {
+                    key: Some(SecretBytes::from(vec![7; 16])),
+                    client_nonce: Some(vec![1; 16]),
+                    server_nonce: Some(vec![2; 16]),
+                },
+                ControlMessage::ServerSync { session: 7 },
+            ]
+        );
+    }
+
+    /// Resync requests go out as a CryptSetup without a key.
+    #[test]
+    fn session_sends_crypt_setup_through_loop() {
+        // Arrange
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transport = TestTransport {
+            sent: Arc::clone(&sent),
+            recv_queue: vec![server_sync(7)],
+            idle_when_empty: true,
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        session.send_crypt_setup(None).expect("send failed");
+        let is_request = |packet: &ControlPacket<Serverbound>| {
+            matches!(
+                packet,
+                ControlPacket::CryptSetup(msg)
+                    if msg.key.is_none() && msg.client_nonce.is_none() && msg.server_nonce.is_none()
+            )
+        };
+        for _ in 0..500 {
+            if sent
+                .lock()
+                .expect("sent lock poisoned")
+                .iter()
+                .any(is_request)
+            {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        // Assert
+        assert!(sent
+            .lock()
+            .expect("sent lock poisoned")
+            .iter()
+            .any(is_request));
+    }
+
+    /// Voice tunnelled over the control stream goes out as UDPTunnel and comes back as voice messages.
+    #[test]
+    fn session_tunnels_voice_packets() {
+        // Arrange
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let tunnelled = VoicePacket::<Clientbound>::Audio {
+            _dst: std::marker::PhantomData,
+            target: 0,
+            session_id: 4,
+            seq_num: 2,
+            payload: VoicePacketPayload::Opus(bytes::Bytes::from_static(b"in"), false),
+            position_info: None,
+        };
+        let transport = TestTransport {
+            sent: Arc::clone(&sent),
+            recv_queue: vec![
+                server_sync(7),
+                ControlPacket::UDPTunnel(Box::new(tunnelled.clone())),
+            ],
+            idle_when_empty: true,
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+        let outgoing = VoicePacket::<Serverbound>::Audio {
+            _dst: std::marker::PhantomData,
+            target: 0,
+            session_id: (),
+            seq_num: 9,
+            payload: VoicePacketPayload::Opus(bytes::Bytes::from_static(b"out"), true),
+            position_info: None,
+        };
+
+        // Act
+        let received = recv_with_retry(session.as_mut()).expect("recv failed");
+        session.send_voice(outgoing.clone()).expect("send failed");
+        let is_tunnel = |packet: &ControlPacket<Serverbound>| matches!(packet, ControlPacket::UDPTunnel(voice) if **voice == outgoing);
+        for _ in 0..500 {
+            if sent
+                .lock()
+                .expect("sent lock poisoned")
+                .iter()
+                .any(is_tunnel)
+            {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        // Assert
+        assert_eq!(received, ControlMessage::Voice(tunnelled));
+        assert!(sent
+            .lock()
+            .expect("sent lock poisoned")
+            .iter()
+            .any(is_tunnel));
+    }
+
+    /// Tunnelled voice shortens control reads so frames leave promptly, and idling restores them.
+    #[test]
+    fn session_shortens_reads_while_tunnelling() {
+        // Arrange
+        let read_timeouts = Arc::new(Mutex::new(Vec::new()));
+        let transport = TestTransport {
+            recv_queue: vec![server_sync(7)],
+            idle_when_empty: true,
+            read_timeouts: Arc::clone(&read_timeouts),
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let handshake = connector.handshake(request()).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+        let frame = VoicePacket::<Serverbound>::Audio {
+            _dst: std::marker::PhantomData,
+            target: 0,
+            session_id: (),
+            seq_num: 1,
+            payload: VoicePacketPayload::Opus(bytes::Bytes::from_static(b"out"), false),
+            position_info: None,
+        };
+
+        // Act
+        session.send_voice(frame).expect("send failed");
+        let deadline = Instant::now() + Duration::from_secs(3);
+        while read_timeouts.lock().expect("timeouts lock poisoned").len() < 2
+            && Instant::now() < deadline
+        {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        // Assert
+        assert_eq!(
+            *read_timeouts.lock().expect("timeouts lock poisoned"),
+            vec![TUNNEL_READ_TIMEOUT, CONTROL_READ_TIMEOUT]
+        );
+    }
+
+    /// Incoming text messages keep the actor and every target list.
+    #[test]
+    fn session_receives_text_messages() {
+        // Arrange
+        let mut text = msgs::TextMessage::new();
+        text.actor = Some(3);
+        text.channel_id = vec![1];
+        text.tree_id = vec![0];
+        text.message = Some("hi all".to_string());
+        let transport = TestTransport {
+            recv_queue: vec![server_sync(7), ControlPacket::TextMessage(Box::new(text))],
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        let message = recv_with_retry(session.as_mut()).expect("recv failed");
+
+        // Assert
+        assert_eq!(
+            message,
+            ControlMessage::TextMessage(TextMessage {
+                actor_id: Some(3),
+                channel_ids: vec![1],
+                tree_ids: vec![0],
+                user_ids: Vec::new(),
+                message: "hi all".to_string(),
+            })
+        );
+    }
+
+    /// The reader loop pings the server and forwards measured round-trip times.
+    #[test]
+    fn session_reports_pong_round_trip() {
+        // Arrange
+        let transport = TestTransport {
+            recv_queue: vec![server_sync(7)],
+            idle_when_empty: true,
+            echo_pings: true,
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        let message = recv_with_retry(session.as_mut()).expect("recv failed");
+
+        // Assert
+        assert!(matches!(message, ControlMessage::Pong { .. }));
+    }
+
+    /// The reader loop gives up when pings stay unanswered past the deadline.
+    #[test]
+    fn session_times_out_without_pong() {
+        // Arrange
+        let transport = TestTransport {
+            recv_queue: vec![server_sync(7)],
+            idle_when_empty: true,
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = HandshakeRequest {
+            keepalive: KeepaliveConfig {
+                interval: Duration::from_millis(5),
+                timeout: Duration::from_millis(20),
+            },
+            ..request()
+        };
+        let handshake = connector.handshake(request).expect("handshake failed");
+        let mut session = handshake.session.expect("missing session");
+
+        // Act
+        let err = recv_with_retry(session.as_mut()).expect_err("expected timeout");
+
+        // Assert
+        assert!(matches!(err, TransportError::Timeout));
+    }
+
+    /// Handshake surfaces transport send failures instead of swallowing them.
+    #[test]
+    fn handshake_propagates_send_error() {
+        // Arrange
+        let transport = TestTransport {
+            send_error: true,
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+
+        // Act
+        let err = connector
+            .handshake(request)
+            .expect_err("expected send failure");
+        // Assert
+        assert!(matches!(err, TransportError::Io(_)));
+    }
+
+    /// Handshake surfaces transport receive failures instead of continuing with stale state.
+    #[test]
+    fn handshake_propagates_recv_error() {
+        // Arrange
+        let transport = TestTransport {
+            recv_error: true,
+            ..Default::default()
+        };
+        let mut connector = MumbleProtocolControlConnector::new(transport);
+        let request = request();
+
         // Act
         let err = connector
             .handshake(request)
@@ -569,12 +1875,7 @@ mod tests {
     fn noop_connector_returns_empty_messages() {
         // Arrange
         let mut connector = super::NoopControlConnector;
-        let request = HandshakeRequest {
-            server: "voice.example".to_string(),
-            port: 64738,
-            username: "alice".to_string(),
-            password: None,
-        };
+        let request = request();
 
         // Act
         let handshake = connector.handshake(request).expect("handshake failed");
@@ -660,7 +1961,10 @@ mod tests {
 
         let captured = Rc::new(RefCell::new(None));
         let captured_clone = Rc::clone(&captured);
-        let mut stream = Some(MemoryStream::with_read_data(out.to_vec()));
+        let peer: SocketAddr = "192.0.2.7:64738".parse().expect("valid address");
+        let mut memory = MemoryStream::with_read_data(out.to_vec());
+        memory.peer = Some(peer);
+        let mut stream = Some(memory);
 
         let mut connector = SocketControlConnector::new(
             move |request: &HandshakeRequest| -> Result<MemoryStream, TransportError> {
@@ -669,12 +1973,7 @@ mod tests {
             },
         );
 
-        let request = HandshakeRequest {
-            server: "voice.example".to_string(),
-            port: 64738,
-            username: "alice".to_string(),
-            password: None,
-        };
+        let request = request();
 
         // Act
         let handshake = connector
@@ -683,6 +1982,8 @@ mod tests {
         let messages = handshake.messages;
         // Assert
         assert_eq!(messages, vec![ControlMessage::ServerSync { session: 9 }]);
+        assert!(handshake.tls.is_none());
+        assert_eq!(handshake.peer, Some(peer));
         assert_eq!(*captured.borrow(), Some(request));
     }
 
@@ -696,12 +1997,7 @@ mod tests {
             },
         );
 
-        let request = HandshakeRequest {
-            server: "voice.example".to_string(),
-            port: 64738,
-            username: "alice".to_string(),
-            password: None,
-        };
+        let request = request();
 
         // Act
         let err = connector
@@ -0,0 +1,308 @@
+use crate::mumble::secret::SecretBytes;
+use crate::transport::errors::TransportError;
+use bytes::BytesMut;
+use mumble_protocol_2x::crypt::{ClientCryptState, DecryptError, BLOCK_SIZE, KEY_SIZE};
+use mumble_protocol_2x::voice::{Clientbound, Serverbound, VoicePacket};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+pub const RESYNC_AFTER: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CryptStats {
+    pub good: u32,
+    pub late: u32,
+    pub lost: u32,
+    pub resync: u32,
+    pub failed: u32,
+}
+
+pub struct VoiceCrypt {
+    state: ClientCryptState,
+    last_good: Instant,
+    last_request: Option<Instant>,
+    resync: u32,
+    failed: u32,
+}
+
+impl VoiceCrypt {
+    pub fn new(
+        key: &SecretBytes,
+        client_nonce: &[u8],
+        server_nonce: &[u8],
+        now: Instant,
+    ) -> Result<Self, TransportError> {
+        let key: [u8; KEY_SIZE] = key
+            .expose()
+            .try_into()
+            .map_err(|_| invalid_setup("key", key.len(), KEY_SIZE))?;
+        // Our nonce encrypts what we send; the server's nonce decrypts what it sends.
+        let state = ClientCryptState::new_from(key, nonce(client_nonce)?, nonce(server_nonce)?);
+        Ok(Self {
+            state,
+            last_good: now,
+            last_request: None,
+            resync: 0,
+            failed: 0,
+        })
+    }
+
+    pub fn client_nonce(&self) -> Vec<u8> {
+        self.state.get_encrypt_nonce().to_vec()
+    }
+
+    pub fn set_server_nonce(&mut self, server_nonce: &[u8]) -> Result<(), TransportError> {
+        self.state.set_decrypt_nonce(&nonce(server_nonce)?);
+        self.resync = self.resync.wrapping_add(1);
+        Ok(())
+    }
+
+    pub fn encrypt(&mut self, packet: VoicePacket<Serverbound>) -> BytesMut {
+        let mut datagram = BytesMut::new();
+        self.state.encrypt(packet, &mut datagram);
+        datagram
+    }
+
+    pub fn decrypt(
+        &mut self,
+        datagram: &[u8],
+        now: Instant,
+    ) -> Result<VoicePacket<Clientbound>, TransportError> {
+        let mut buffer = BytesMut::from(datagram);
+        match self.state.decrypt(&mut buffer) {
+            Ok(packet) => {
+                self.last_good = now;
+                packet
+                    .map_err(|err| TransportError::Protocol(format!("invalid voice packet: {err}")))
+            }
+            Err(err) => {
+                // The crypt state keeps no count of rejected datagrams, so track them here.
+                self.failed = self.failed.wrapping_add(1);
+                Err(TransportError::Protocol(format!(
+                    "voice decrypt failed: {}",
+                    describe(err)
+                )))
+            }
+        }
+    }
+
+    pub fn resync_due(&mut self, now: Instant) -> bool {
+        // Called after a failed decrypt. Like the reference client, ask for a fresh server
+        // nonce only once nothing has decrypted for a while, and not more often than that.
+        let stale = now.saturating_duration_since(self.last_good) > RESYNC_AFTER;
+        let quiet = self
+            .last_request
+            .map_or(true, |at| now.saturating_duration_since(at) > RESYNC_AFTER);
+        if stale && quiet {
+            self.last_request = Some(now);
+            return true;
+        }
+        false
+    }
+
+    pub fn stats(&self) -> CryptStats {
+        CryptStats {
+            good: self.state.get_good(),
+            late: self.state.get_late(),
+            lost: self.state.get_lost(),
+            resync: self.resync,
+            failed: self.failed,
+        }
+    }
+}
+
+impl fmt::Debug for VoiceCrypt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VoiceCrypt")
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+fn nonce(value: &[u8]) -> Result<[u8; BLOCK_SIZE], TransportError> {
+    value
+        .try_into()
+        .map_err(|_| invalid_setup("nonce", value.len(), BLOCK_SIZE))
+}
+
+fn invalid_setup(field: &str, actual: usize, expected: usize) -> TransportError {
+    TransportError::Protocol(format!(
+        "invalid crypt setup {field}: {actual} bytes, expected {expected}"
+    ))
+}
+
+fn describe(err: DecryptError) -> &'static str {
+    match err {
+        DecryptError::Eof => "packet too short",
+        DecryptError::Repeat => "packet repeated",
+        DecryptError::Late => "packet too late",
+        DecryptError::Mac => "authentication failed",
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{CryptStats, VoiceCrypt, RESYNC_AFTER};
+    use crate::mumble::secret::SecretBytes;
+    use crate::transport::errors::TransportError;
+    use bytes::{Bytes, BytesMut};
+    use mumble_protocol_2x::crypt::ServerCryptState;
+    use mumble_protocol_2x::voice::{Clientbound, VoicePacket, VoicePacketPayload};
+    use std::marker::PhantomData;
+    use std::time::{Duration, Instant};
+
+    pub(crate) const KEY: [u8; 16] = [7; 16];
+    pub(crate) const CLIENT_NONCE: [u8; 16] = [1; 16];
+    pub(crate) const SERVER_NONCE: [u8; 16] = [2; 16];
+
+    pub(crate) fn server_state() -> ServerCryptState {
+        ServerCryptState::new_from(KEY, SERVER_NONCE, CLIENT_NONCE)
+    }
+
+    pub(crate) fn client_crypt(now: Instant) -> VoiceCrypt {
+        VoiceCrypt::new(
+            &SecretBytes::from(KEY.to_vec()),
+            &CLIENT_NONCE,
+            &SERVER_NONCE,
+            now,
+        )
+        .expect("crypt setup failed")
+    }
+
+    pub(crate) fn audio(session_id: u32, seq_num: u64) -> VoicePacket<Clientbound> {
+        VoicePacket::Audio {
+            _dst: PhantomData,
+            target: 0,
+            session_id,
+            seq_num,
+            payload: VoicePacketPayload::Opus(Bytes::from_static(b"opus"), false),
+            position_info: None,
+        }
+    }
+
+    fn seal(server: &mut ServerCryptState, packet: VoicePacket<Clientbound>) -> BytesMut {
+        let mut datagram = BytesMut::new();
+        server.encrypt(packet, &mut datagram);
+        datagram
+    }
+
+    /// Packets sealed by the server decrypt in order and count as good.
+    #[test]
+    fn decrypt_accepts_server_packets() {
+        // Arrange
+        let now = Instant::now();
+        let mut server = server_state();
+        let mut crypt = client_crypt(now);
+        let datagram = seal(&mut server, audio(3, 10));
+
+        // Act
+        let packet = crypt.decrypt(&datagram, now).expect("decrypt failed");
+
+        // Assert
+        assert_eq!(packet, audio(3, 10));
+        assert_eq!(
+            crypt.stats(),
+            CryptStats {
+                good: 1,
+                ..CryptStats::default()
+            }
+        );
+    }
+
+    /// Client packets decrypt on the server side with the matching nonce.
+    #[test]
+    fn encrypt_produces_server_readable_packets() {
+        // Arrange
+        let mut server = server_state();
+        let mut crypt = client_crypt(Instant::now());
+
+        // Act
+        let mut datagram = crypt.encrypt(VoicePacket::Ping { timestamp: 42 });
+        let packet = server
+            .decrypt(&mut datagram)
+            .expect("decrypt failed")
+            .expect("parse failed");
+
+        // Assert
+        assert_eq!(packet, VoicePacket::Ping { timestamp: 42 });
+        assert_ne!(crypt.client_nonce(), CLIENT_NONCE.to_vec());
+    }
+
+    /// Gaps in the nonce sequence count as lost, out-of-order packets as late and
+    /// rejected datagrams as failed.
+    #[test]
+    fn decrypt_counts_lost_and_late_packets() {
+        // Arrange
+        let now = Instant::now();
+        let mut server = server_state();
+        let mut crypt = client_crypt(now);
+        let first = seal(&mut server, audio(3, 1));
+        let skipped = seal(&mut server, audio(3, 2));
+        let third = seal(&mut server, audio(3, 3));
+        seal(&mut server, audio(3, 4));
+        let fifth = seal(&mut server, audio(3, 5));
+
+        // Act
+        crypt.decrypt(&first, now).expect("decrypt failed");
+        crypt.decrypt(&third, now).expect("decrypt failed");
+        crypt.decrypt(&skipped, now).expect("decrypt failed");
+        let repeated = crypt.decrypt(&third, now);
+        crypt.decrypt(&fifth, now).expect("decrypt failed");
+
+        // Assert
+        assert_eq!(
+            crypt.stats(),
+            CryptStats {
+                good: 4,
+                late: 1,
+                lost: 1,
+                resync: 0,
+                failed: 1,
+            }
+        );
+        assert!(matches!(repeated, Err(TransportError::Protocol(_))));
+    }
+
+    /// A resync is requested once decryption has failed for a while, then rate limited.
+    #[test]
+    fn resync_due_after_failures() {
+        // Arrange
+        let start = Instant::now();
+        let mut server = ServerCryptState::new_from(KEY, [9; 16], CLIENT_NONCE);
+        let mut crypt = client_crypt(start);
+        let datagram = seal(&mut server, audio(3, 1));
+        let stale = start + RESYNC_AFTER + Duration::from_secs(1);
+
+        // Act
+        let failed = crypt.decrypt(&datagram, start);
+        let early = crypt.resync_due(start + Duration::from_secs(1));
+        let due = crypt.resync_due(stale);
+        let repeated = crypt.resync_due(stale + Duration::from_secs(1));
+        crypt.set_server_nonce(&[9; 16]).expect("resync failed");
+        let recovered = crypt.decrypt(&datagram, stale);
+
+        // Assert
+        assert!(failed.is_err());
+        assert!(!early);
+        assert!(due);
+        assert!(!repeated);
+        assert!(recovered.is_ok());
+        assert_eq!(crypt.stats().resync, 1);
+    }
+
+    /// Keys and nonces of the wrong size are protocol errors.
+    #[test]
+    fn new_rejects_malformed_setup() {
+        // Arrange
+        let short_key = SecretBytes::from(vec![1; 8]);
+        let key = SecretBytes::from(KEY.to_vec());
+
+        // Act
+        let bad_key = VoiceCrypt::new(&short_key, &CLIENT_NONCE, &SERVER_NONCE, Instant::now());
+        let bad_nonce = VoiceCrypt::new(&key, &[1; 4], &SERVER_NONCE, Instant::now());
+
+        // Assert
+        assert!(matches!(bad_key, Err(TransportError::Protocol(_))));
+        assert!(matches!(bad_nonce, Err(TransportError::Protocol(_))));
+    }
+}
@@ -0,0 +1,707 @@
+use crate::mumble::config::MumbleConfig;
+use crate::mumble::connect::{dial, resolve};
+use crate::mumble::control::{
+    BlockingControlTransport, ControlConnector, ControlMessage, MumbleProtocolControlConnector,
+    CONTROL_READ_TIMEOUT,
+};
+use crate::mumble::tls::{peer_chain, tls_handshake, verify_peer, CertificateInfo};
+use crate::transport::errors::TransportError;
+use crate::transport::types::{ProtocolVersion, ServerInfo};
+use std::fmt;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+pub const UDP_PING_TIMEOUT: Duration = Duration::from_secs(1);
+pub const UDP_PING_ATTEMPTS: u32 = 3;
+const UDP_PING_REQUEST_LEN: usize = 12;
+const UDP_PING_REPLY_LEN: usize = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticStage {
+    Resolve,
+    TcpConnect,
+    TlsHandshake,
+    Authenticate,
+    UdpPing,
+}
+
+impl fmt::Display for DiagnosticStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            DiagnosticStage::Resolve => "resolve",
+            DiagnosticStage::TcpConnect => "tcp connect",
+            DiagnosticStage::TlsHandshake => "tls handshake",
+            DiagnosticStage::Authenticate => "version/authenticate",
+            DiagnosticStage::UdpPing => "udp ping",
+        };
+        write!(f, "{text}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StageStatus {
+    Passed,
+    Failed(String),
+    Skipped(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageResult {
+    pub stage: DiagnosticStage,
+    pub status: StageStatus,
+    pub elapsed: Duration,
+    pub details: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServerPing {
+    pub version: ProtocolVersion,
+    pub users: u32,
+    pub max_users: u32,
+    pub max_bandwidth: u32,
+    pub rtt: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiagnosticReport {
+    pub server: String,
+    pub port: u16,
+    pub stages: Vec<StageResult>,
+    pub addresses: Vec<SocketAddr>,
+    pub certificate_chain: Vec<CertificateInfo>,
+    pub fingerprint: Option<String>,
+    pub server_info: Option<ServerInfo>,
+    pub udp_ping: Option<ServerPing>,
+}
+
+impl DiagnosticReport {
+    pub fn passed(&self) -> bool {
+        !self
+            .stages
+            .iter()
+            .any(|stage| matches!(stage.status, StageStatus::Failed(_)))
+    }
+
+    pub fn stage(&self, stage: DiagnosticStage) -> Option<&StageResult> {
+        self.stages.iter().find(|result| result.stage == stage)
+    }
+
+    pub fn summary(&self) -> String {
+        let mut text = format!("Diagnostics for {}:{}\n", self.server, self.port);
+        for result in &self.stages {
+            let elapsed = result.elapsed.as_millis();
+            let line = match &result.status {
+                StageStatus::Passed => format!("[PASS] {} ({elapsed} ms)", result.stage),
+                StageStatus::Failed(reason) => {
+                    format!("[FAIL] {} ({elapsed} ms): {reason}", result.stage)
+                }
+                StageStatus::Skipped(reason) => format!("[SKIP] {}: {reason}", result.stage),
+            };
+            text.push_str(&line);
+            text.push('\n');
+            for detail in &result.details {
+                text.push_str(&format!("    {detail}\n"));
+            }
+        }
+        let result = if self.passed() { "passed" } else { "failed" };
+        text.push_str(&format!("Result: {result}\n"));
+        text
+    }
+
+    fn record(
+        &mut self,
+        stage: DiagnosticStage,
+        started: Instant,
+        status: StageStatus,
+        details: Vec<String>,
+    ) {
+        self.stages.push(StageResult {
+            stage,
+            status,
+            elapsed: started.elapsed(),
+            details,
+        });
+    }
+
+    fn skip_remaining(&mut self, reason: &str) {
+        let stages = [
+            DiagnosticStage::Resolve,
+            DiagnosticStage::TcpConnect,
+            DiagnosticStage::TlsHandshake,
+            DiagnosticStage::Authenticate,
+            DiagnosticStage::UdpPing,
+        ];
+        for stage in stages {
+            if self.stage(stage).is_none() {
+                self.stages.push(StageResult {
+                    stage,
+                    status: StageStatus::Skipped(reason.to_string()),
+                    elapsed: Duration::ZERO,
+                    details: Vec::new(),
+                });
+            }
+        }
+    }
+}
+
+pub fn run_diagnostics(config: &MumbleConfig, live_session: bool) -> DiagnosticReport {
+    let mut report = DiagnosticReport {
+        server: config.server.clone(),
+        port: config.port,
+        stages: Vec::new(),
+        addresses: Vec::new(),
+        certificate_chain: Vec::new(),
+        fingerprint: None,
+        server_info: None,
+        udp_ping: None,
+    };
+
+    let started = Instant::now();
+    match resolve(&config.server, config.port) {
+        Ok(addresses) => {
+            let details = addresses.iter().map(SocketAddr::to_string).collect();
+            report.addresses = addresses;
+            report.record(
+                DiagnosticStage::Resolve,
+                started,
+                StageStatus::Passed,
+                details,
+            );
+        }
+        Err(error) => {
+            report.record(
+                DiagnosticStage::Resolve,
+                started,
+                StageStatus::Failed(error.to_string()),
+                Vec::new(),
+            );
+            // A proxy resolves the server itself, so only a direct connection stops here.
+            if config.proxy.is_none() {
+                report.skip_remaining("server address could not be resolved");
+                return report;
+            }
+        }
+    }
+
+    let started = Instant::now();
+    let tcp = match dial(
+        &config.server,
+        config.port,
+        config.proxy.as_ref(),
+        config.timeouts,
+    ) {
+        Ok(tcp) => {
+            let mut details = Vec::new();
+            if let Some(proxy) = &config.proxy {
+                details.push(format!(
+                    "via {:?} proxy {}:{}",
+                    proxy.kind, proxy.server, proxy.port
+                ));
+            } else if let Ok(peer) = tcp.peer_addr() {
+                details.push(format!("connected to {peer}"));
+            }
+            report.record(
+                DiagnosticStage::TcpConnect,
+                started,
+                StageStatus::Passed,
+                details,
+            );
+            Some(tcp)
+        }
+        Err(error) => {
+            report.record(
+                DiagnosticStage::TcpConnect,
+                started,
+                StageStatus::Failed(error.to_string()),
+                Vec::new(),
+            );
+            None
+        }
+    };
+    let udp_target = match &tcp {
+        Some(tcp) if config.proxy.is_none() => tcp.peer_addr().ok(),
+        _ => report.addresses.first().copied(),
+    };
+
+    if let Some(tcp) = tcp {
+        diagnose_control(config, live_session, tcp, &mut report);
+    }
+    report.skip_remaining("tcp connection failed");
+
+    report
+        .stages
+        .retain(|stage| stage.stage != DiagnosticStage::UdpPing);
+    let started = Instant::now();
+    match (&config.proxy, udp_target) {
+        (Some(_), _) => report.record(
+            DiagnosticStage::UdpPing,
+            started,
+            StageStatus::Skipped("UDP is not carried through the proxy".to_string()),
+            Vec::new(),
+        ),
+        (None, None) => report.record(
+            DiagnosticStage::UdpPing,
+            started,
+            StageStatus::Skipped("no server address to ping".to_string()),
+            Vec::new(),
+        ),
+        (None, Some(address)) => {
+            match udp_server_ping(address, UDP_PING_TIMEOUT, UDP_PING_ATTEMPTS) {
+                Ok(ping) => {
+                    report.udp_ping = Some(ping);
+                    report.record(
+                        DiagnosticStage::UdpPing,
+                        started,
+                        StageStatus::Passed,
+                        vec![
+                            format!("round trip {} ms", ping.rtt.as_millis()),
+                            format!("{}/{} users", ping.users, ping.max_users),
+                        ],
+                    );
+                }
+                Err(error) => report.record(
+                    DiagnosticStage::UdpPing,
+                    started,
+                    StageStatus::Failed(error.to_string()),
+                    vec![format!("pinged {address}")],
+                ),
+            }
+        }
+    }
+    report
+}
+
+fn diagnose_control(
+    config: &MumbleConfig,
+    live_session: bool,
+    tcp: std::net::TcpStream,
+    report: &mut DiagnosticReport,
+) {
+    let started = Instant::now();
+    let request = match config.handshake_request() {
+        Ok(request) => request,
+        Err(error) => {
+            report.record(
+                DiagnosticStage::TlsHandshake,
+                started,
+                StageStatus::Failed(error.to_string()),
+                Vec::new(),
+            );
+            report.skip_remaining("configuration is invalid");
+            return;
+        }
+    };
+    let stream = tcp
+        .set_read_timeout(Some(request.timeouts.handshake))
+        .and_then(|_| tcp.set_write_timeout(Some(request.timeouts.handshake)))
+        .map_err(TransportError::from)
+        .and_then(|_| tls_handshake(&request, tcp));
+    let stream = match stream {
+        Ok(stream) => stream,
+        Err(error) => {
+            report.record(
+                DiagnosticStage::TlsHandshake,
+                started,
+                StageStatus::Failed(error.to_string()),
+                Vec::new(),
+            );
+            report.skip_remaining("tls handshake failed");
+            return;
+        }
+    };
+
+    let ssl = stream.ssl();
+    let mut details = vec![format!(
+        "{} using {}",
+        ssl.version_str(),
+        ssl.current_cipher()
+            .map(|cipher| cipher.name())
+            .unwrap_or("unknown cipher")
+    )];
+    match peer_chain(ssl) {
+        Ok(chain) => {
+            for certificate in &chain {
+                details.push(format!(
+                    "certificate {} issued by {} valid until {}",
+                    certificate.subject, certificate.issuer, certificate.not_after
+                ));
+            }
+            report.fingerprint = chain.first().map(|leaf| leaf.fingerprint.clone());
+            report.certificate_chain = chain;
+        }
+        Err(error) => details.push(format!("certificate chain unavailable: {error}")),
+    }
+    if let Some(fingerprint) = &report.fingerprint {
+        details.push(format!("fingerprint {fingerprint}"));
+    }
+    if let Err(error) = verify_peer(&request, ssl) {
+        report.record(
+            DiagnosticStage::TlsHandshake,
+            started,
+            StageStatus::Failed(error.to_string()),
+            details,
+        );
+        report.skip_remaining("server certificate is not trusted");
+        return;
+    }
+    report.record(
+        DiagnosticStage::TlsHandshake,
+        started,
+        StageStatus::Passed,
+        details,
+    );
+
+    let started = Instant::now();
+    // Logging in again with the same user and certificate would kick the live session.
+    if live_session {
+        report.record(
+            DiagnosticStage::Authenticate,
+            started,
+            StageStatus::Skipped("already connected with this account".to_string()),
+            Vec::new(),
+        );
+        return;
+    }
+    // The control loop started after sync must notice the close promptly.
+    if let Err(error) = stream
+        .get_ref()
+        .set_read_timeout(Some(CONTROL_READ_TIMEOUT))
+    {
+        report.record(
+            DiagnosticStage::Authenticate,
+            started,
+            StageStatus::Failed(TransportError::from(error).to_string()),
+            Vec::new(),
+        );
+        return;
+    }
+    let mut connector = MumbleProtocolControlConnector::new(BlockingControlTransport::new(stream));
+    match connector.handshake(request) {
+        Ok(handshake) => {
+            let mut details = Vec::new();
+            for message in &handshake.messages {
+                match message {
+                    ControlMessage::ServerVersion(info) => {
+                        report.server_info = Some(info.clone());
+                        if let Some(version) = info.version {
+                            details.push(format!(
+                                "server version {}.{}.{}",
+                                version.major, version.minor, version.patch
+                            ));
+                        }
+                        if let Some(release) = &info.release {
+                            details.push(format!("server release {release}"));
+                        }
+                    }
+                    ControlMessage::ServerSync { session } => {
+                        details.push(format!("synced as session {session}"));
+                    }
+                    _ => {}
+                }
+            }
+            let status = match handshake.session.map(|mut session| session.close()) {
+                Some(Err(error)) => StageStatus::Failed(format!("disconnect failed: {error}")),
+                _ => StageStatus::Passed,
+            };
+            report.record(DiagnosticStage::Authenticate, started, status, details);
+        }
+        Err(error) => report.record(
+            DiagnosticStage::Authenticate,
+            started,
+            StageStatus::Failed(error.to_string()),
+            Vec::new(),
+        ),
+    }
+}
+
+pub fn udp_server_ping(
+    address: SocketAddr,
+    timeout: Duration,
+    attempts: u32,
+) -> Result<ServerPing, TransportError> {
+    let bind: SocketAddr = if address.is_ipv6() {
+        "[::]:0".parse().expect("valid bind address")
+    } else {
+        "0.0.0.0:0".parse().expect("valid bind address")
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect(address)?;
+
+    for attempt in 0..attempts {
+        // Servers echo the identifier back, which is how replies are matched to requests.
+        let ident = u64::from(attempt) + 1;
+        let mut request = [0u8; UDP_PING_REQUEST_LEN];
+        request[4..].copy_from_slice(&ident.to_be_bytes());
+        let sent = Instant::now();
+        socket.send(&request)?;
+
+        let deadline = sent + timeout;
+        let mut reply = [0u8; UDP_PING_REPLY_LEN];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            match socket.recv(&mut reply) {
+                Ok(UDP_PING_REPLY_LEN) if reply[4..12] == ident.to_be_bytes() => {
+                    let field = |offset: usize| {
+                        u32::from_be_bytes(
+                            reply[offset..offset + 4]
+                                .try_into()
+                                .expect("reply length checked"),
+                        )
+                    };
+                    return Ok(ServerPing {
+                        version: ProtocolVersion::from_v1(field(0)),
+                        users: field(12),
+                        max_users: field(16),
+                        max_bandwidth: field(20),
+                        rtt: sent.elapsed(),
+                    });
+                }
+                Ok(_) => {}
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    break
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+    Err(TransportError::Timeout)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{run_diagnostics, udp_server_ping, DiagnosticStage, StageStatus};
+    use crate::mumble::certificate::tests::self_signed;
+    use crate::mumble::config::MumbleConfig;
+    use crate::mumble::known_hosts::certificate_fingerprint;
+    use crate::transport::errors::TransportError;
+    use bytes::BytesMut;
+    use mumble_protocol_2x::control::{msgs, ControlPacket, ServerControlCodec};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, UdpSocket};
+    use std::thread::JoinHandle;
+    use std::time::Duration;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    pub(crate) struct StandIn {
+        pub(crate) port: u16,
+        pub(crate) fingerprint: String,
+        pub(crate) control: JoinHandle<Vec<String>>,
+        pub(crate) udp: JoinHandle<()>,
+    }
+
+    pub(crate) fn stand_in_server() -> StandIn {
+        let (certificate, key) = self_signed("localhost");
+        let fingerprint = certificate_fingerprint(&certificate).expect("fingerprint failed");
+        let mut acceptor =
+            openssl::ssl::SslAcceptor::mozilla_intermediate_v5(openssl::ssl::SslMethod::tls())
+                .expect("acceptor failed");
+        acceptor.set_certificate(&certificate).expect("cert failed");
+        acceptor.set_private_key(&key).expect("key failed");
+        let acceptor = acceptor.build();
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind failed");
+        let port = listener.local_addr().expect("missing address").port();
+        let udp = UdpSocket::bind(("127.0.0.1", port)).expect("udp bind failed");
+
+        let control = std::thread::spawn(move || {
+            let (tcp, _) = listener.accept().expect("accept failed");
+            let Ok(mut stream) = acceptor.accept(tcp) else {
+                return Vec::new();
+            };
+            let mut codec = ServerControlCodec::new();
+            let mut buffer = BytesMut::new();
+            let mut received = Vec::new();
+            loop {
+                match codec.decode(&mut buffer) {
+                    Ok(Some(packet)) => {
+                        let authenticated = matches!(packet, ControlPacket::Authenticate(_));
+                        received.push(packet.name().to_string());
+                        if authenticated {
+                            break;
+                        }
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(_) => return received,
+                }
+                let mut chunk = [0u8; 1024];
+                match stream.read(&mut chunk) {
+                    Ok(0) | Err(_) => return received,
+                    Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+                }
+            }
+
+            let mut version = msgs::Version::new();
+            version.version_v1 = Some(0x0001_0500);
+            version.release = Some("stand-in".to_string());
+            let mut sync = msgs::ServerSync::new();
+            sync.session = Some(7);
+            let mut out = BytesMut::new();
+            codec
+                .encode(ControlPacket::Version(Box::new(version)), &mut out)
+                .expect("encode failed");
+            codec
+                .encode(ControlPacket::ServerSync(Box::new(sync)), &mut out)
+                .expect("encode failed");
+            stream.write_all(&out).expect("write failed");
+            let mut rest = [0u8; 1024];
+            while matches!(stream.read(&mut rest), Ok(read) if read > 0) {}
+            received
+        });
+
+        let udp = std::thread::spawn(move || {
+            udp.set_read_timeout(Some(Duration::from_secs(5)))
+                .expect("timeout failed");
+            let mut request = [0u8; 12];
+            let Ok((_, client)) = udp.recv_from(&mut request) else {
+                return;
+            };
+            let mut reply = Vec::new();
+            reply.extend(0x0001_0500u32.to_be_bytes());
+            reply.extend(&request[4..]);
+            reply.extend(3u32.to_be_bytes());
+            reply.extend(100u32.to_be_bytes());
+            reply.extend(72_000u32.to_be_bytes());
+            udp.send_to(&reply, client).expect("udp send failed");
+        });
+
+        StandIn {
+            port,
+            fingerprint,
+            control,
+            udp,
+        }
+    }
+
+    fn config(port: u16) -> MumbleConfig {
+        MumbleConfig::new("127.0.0.1".to_string(), port, "alice".to_string())
+    }
+
+    /// Every stage passes against a reachable, pinned server and the report carries its details.
+    #[test]
+    fn run_diagnostics_passes_all_stages() {
+        // Arrange
+        let server = stand_in_server();
+        let mut config = config(server.port);
+        config.trusted_fingerprint = Some(server.fingerprint.clone());
+
+        // Act
+        let report = run_diagnostics(&config, false);
+        let received = server.control.join().expect("control panicked");
+        server.udp.join().expect("udp panicked");
+
+        // Assert
+        assert!(report.passed(), "{}", report.summary());
+        let stages: Vec<_> = report.stages.iter().map(|stage| stage.stage).collect();
+        assert_eq!(
+            stages,
+            [
+                DiagnosticStage::Resolve,
+                DiagnosticStage::TcpConnect,
+                DiagnosticStage::TlsHandshake,
+                DiagnosticStage::Authenticate,
+                DiagnosticStage::UdpPing,
+            ]
+        );
+        assert_eq!(received, ["Version", "Authenticate"]);
+        assert_eq!(
+            report.fingerprint.as_deref(),
+            Some(server.fingerprint.as_str())
+        );
+        assert_eq!(report.certificate_chain.len(), 1);
+        assert_eq!(report.certificate_chain[0].subject, "CN=localhost");
+        assert_eq!(
+            report
+                .server_info
+                .as_ref()
+                .and_then(|info| info.release.as_deref()),
+            Some("stand-in")
+        );
+        let ping = report.udp_ping.expect("missing udp ping");
+        assert_eq!((ping.users, ping.max_users), (3, 100));
+        assert!(report.summary().contains("[PASS] udp ping"));
+    }
+
+    /// An untrusted certificate fails TLS with its fingerprint, skips authentication and still pings UDP.
+    #[test]
+    fn run_diagnostics_reports_untrusted_certificate() {
+        // Arrange
+        let server = stand_in_server();
+        let config = config(server.port);
+
+        // Act
+        let report = run_diagnostics(&config, false);
+        let received = server.control.join().expect("control panicked");
+        server.udp.join().expect("udp panicked");
+
+        // Assert
+        assert!(!report.passed());
+        let tls = report
+            .stage(DiagnosticStage::TlsHandshake)
+            .expect("missing tls stage");
+        assert!(
+            matches!(tls.status, StageStatus::Failed(ref reason) if reason.contains("untrusted"))
+        );
+        assert!(tls
+            .details
+            .iter()
+            .any(|detail| detail.contains(&server.fingerprint)));
+        assert!(matches!(
+            report
+                .stage(DiagnosticStage::Authenticate)
+                .map(|stage| &stage.status),
+            Some(StageStatus::Skipped(_))
+        ));
+        assert_eq!(
+            report
+                .stage(DiagnosticStage::UdpPing)
+                .map(|stage| &stage.status),
+            Some(&StageStatus::Passed)
+        );
+        assert!(received.is_empty());
+    }
+
+    /// Resolution failures stop the run and the summary names the failing stage.
+    #[test]
+    fn run_diagnostics_stops_after_resolve_failure() {
+        // Arrange
+        let config = MumbleConfig::new("bad host name".to_string(), 64738, "alice".to_string());
+
+        // Act
+        let report = run_diagnostics(&config, false);
+
+        // Assert
+        assert!(!report.passed());
+        assert_eq!(report.stages.len(), 5);
+        assert!(report
+            .stages
+            .iter()
+            .skip(1)
+            .all(|stage| matches!(stage.status, StageStatus::Skipped(_))));
+        let summary = report.summary();
+        assert!(summary.contains("[FAIL] resolve"));
+        assert!(summary.ends_with("Result: failed\n"));
+    }
+
+    /// A silent UDP port times out after the configured attempts.
+    #[test]
+    fn udp_server_ping_times_out_without_reply() {
+        // Arrange
+        let silent = UdpSocket::bind("127.0.0.1:0").expect("bind failed");
+        let address = silent.local_addr().expect("missing address");
+
+        // Act
+        let result = udp_server_ping(address, Duration::from_millis(20), 2);
+
+        // Assert
+        assert!(matches!(result, Err(TransportError::Timeout)));
+    }
+}
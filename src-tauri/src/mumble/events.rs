@@ -1,18 +1,42 @@
-use crate::transport::types::{Channel, ConnState, User};
+use std::time::Duration;
 
-#[derive(Clone, Debug)]
+use crate::mumble::tls::TlsSessionInfo;
+use crate::mumble::voice::VoicePath;
+use crate::transport::types::{Channel, ConnState, ServerInfo, UntrustedCertificate, User};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TextMessage {
     pub actor_id: Option<u32>,
-    pub channel_id: Option<u32>,
+    pub channel_ids: Vec<u32>,
+    pub tree_ids: Vec<u32>,
     pub user_ids: Vec<u32>,
     pub message: String,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemovalKind {
+    Kick,
+    Ban,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemovalNotice {
+    pub kind: RemovalKind,
+    pub actor_id: Option<u32>,
+    pub reason: Option<String>,
+}
+
 #[derive(Clone, Debug)]
 pub enum TransportEvent {
     ConnectionState(ConnState),
+    ServerInfo(ServerInfo),
+    TlsSession(TlsSessionInfo),
     Channels(Vec<Channel>),
     Users(Vec<User>),
     Text(TextMessage),
+    Latency(Duration),
+    VoicePath(VoicePath),
+    Removed(RemovalNotice),
+    UntrustedCertificate(UntrustedCertificate),
     Error(String),
 }
@@ -0,0 +1,356 @@
+use crate::mumble::certificate::ClientIdentity;
+use crate::mumble::config::MumbleConfig;
+use crate::mumble::secret::SecretString;
+use crate::mumble::vault::write_private;
+use crate::transport::errors::TransportError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const IDENTITY_DIR: &str = "identities";
+const IDENTITY_EXTENSION: &str = "pem";
+const SELECTED_FILE: &str = "selected";
+pub const DEFAULT_IDENTITY: &str = "default";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+    pub name: String,
+    pub cert_pem: SecretString,
+}
+
+impl Identity {
+    pub fn apply_to(&self, config: &mut MumbleConfig) {
+        config.cert_pem = Some(self.cert_pem.clone());
+        config.cert_pkcs12 = None;
+        config.cert_password = None;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IdentityStore {
+    root: PathBuf,
+}
+
+impl IdentityStore {
+    pub fn new(app_data_dir: &Path) -> Self {
+        Self {
+            root: app_data_dir.join(IDENTITY_DIR),
+        }
+    }
+
+    pub fn list(&self) -> Result<Vec<String>, TransportError> {
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(IDENTITY_EXTENSION) {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn load(&self, name: &str) -> Result<Identity, TransportError> {
+        let path = self.identity_path(name)?;
+        let cert_pem = match fs::read_to_string(&path) {
+            Ok(cert_pem) => SecretString::from(cert_pem),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(TransportError::InvalidConfig(format!(
+                    "unknown identity: {name}"
+                )));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Identity {
+            name: name.to_string(),
+            cert_pem,
+        })
+    }
+
+    pub fn generate(&self, name: &str) -> Result<Identity, TransportError> {
+        let path = self.identity_path(name)?;
+        if path.exists() {
+            return Err(TransportError::InvalidConfig(format!(
+                "identity already exists: {name}"
+            )));
+        }
+
+        let cert_pem = ClientIdentity::generate(name)?.to_pem()?;
+        self.save(name, cert_pem.expose())?;
+        Ok(Identity {
+            name: name.to_string(),
+            cert_pem,
+        })
+    }
+
+    pub fn import_pkcs12(
+        &self,
+        name: &str,
+        der: &[u8],
+        password: Option<&str>,
+    ) -> Result<Identity, TransportError> {
+        let path = self.identity_path(name)?;
+        if path.exists() {
+            return Err(TransportError::InvalidConfig(format!(
+                "identity already exists: {name}"
+            )));
+        }
+
+        let cert_pem = ClientIdentity::from_pkcs12(der, password)?.to_pem()?;
+        self.save(name, cert_pem.expose())?;
+        Ok(Identity {
+            name: name.to_string(),
+            cert_pem,
+        })
+    }
+
+    pub fn export_pkcs12(
+        &self,
+        name: &str,
+        password: Option<&str>,
+    ) -> Result<Vec<u8>, TransportError> {
+        let identity = self.load(name)?;
+        ClientIdentity::from_pem(identity.cert_pem.expose().as_bytes(), None)?
+            .to_pkcs12(name, password)
+    }
+
+    pub fn select(&self, name: &str) -> Result<(), TransportError> {
+        self.load(name)?;
+        write_private(&self.root.join(SELECTED_FILE), name.as_bytes())
+    }
+
+    pub fn selected(&self) -> Result<Option<Identity>, TransportError> {
+        let name = match fs::read_to_string(self.root.join(SELECTED_FILE)) {
+            Ok(name) => name,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        self.load(name.trim()).map(Some)
+    }
+
+    pub fn ensure_selected(&self) -> Result<Identity, TransportError> {
+        if let Some(identity) = self.selected()? {
+            return Ok(identity);
+        }
+
+        // First run: give the user a stable identity without asking them about certificates.
+        let identity = match self.list()?.first() {
+            Some(name) => self.load(name)?,
+            None => self.generate(DEFAULT_IDENTITY)?,
+        };
+        self.select(&identity.name)?;
+        Ok(identity)
+    }
+
+    pub(crate) fn save(&self, name: &str, cert_pem: &str) -> Result<(), TransportError> {
+        let path = self.identity_path(name)?;
+        write_private(&path, cert_pem.as_bytes())
+    }
+
+    fn identity_path(&self, name: &str) -> Result<PathBuf, TransportError> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(TransportError::InvalidConfig(format!(
+                "invalid identity name: {name}"
+            )));
+        }
+        Ok(self.root.join(name).with_extension(IDENTITY_EXTENSION))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{IdentityStore, DEFAULT_IDENTITY};
+    use crate::mumble::certificate::{ClientCertificate, ClientIdentity};
+    use crate::mumble::config::MumbleConfig;
+    use crate::transport::errors::TransportError;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub(crate) struct TempDir(pub(crate) PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "babble-test-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::SeqCst)
+            ));
+            std::fs::create_dir_all(&path).expect("temp dir failed");
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// An empty store lists nothing and has no selection.
+    #[test]
+    fn empty_store_has_no_identities() {
+        // Arrange
+        let dir = TempDir::new();
+        let store = IdentityStore::new(&dir.0);
+
+        // Act
+        let names = store.list().expect("list failed");
+        let selected = store.selected().expect("selected failed");
+
+        // Assert
+        assert!(names.is_empty());
+        assert!(selected.is_none());
+    }
+
+    /// First run generates, stores and selects a default identity, and later runs reuse it.
+    #[test]
+    fn ensure_selected_generates_once() {
+        // Arrange
+        let dir = TempDir::new();
+        let store = IdentityStore::new(&dir.0);
+
+        // Act
+        let first = store.ensure_selected().expect("first run failed");
+        let second = IdentityStore::new(&dir.0)
+            .ensure_selected()
+            .expect("second run failed");
+
+        // Assert
+        assert_eq!(first.name, DEFAULT_IDENTITY);
+        assert_eq!(first, second);
+        assert_eq!(store.list().expect("list failed"), vec![DEFAULT_IDENTITY]);
+        let loaded = ClientIdentity::load(&ClientCertificate::Pem {
+            bundle: first.cert_pem,
+            password: None,
+        });
+        assert!(loaded.is_ok());
+    }
+
+    /// Identities can be listed and switched between.
+    #[test]
+    fn select_switches_identity() {
+        // Arrange
+        let dir = TempDir::new();
+        let store = IdentityStore::new(&dir.0);
+        store.generate("work").expect("generate failed");
+        let home = store.generate("home").expect("generate failed");
+
+        // Act
+        store.select("home").expect("select failed");
+
+        // Assert
+        assert_eq!(store.list().expect("list failed"), vec!["home", "work"]);
+        assert_eq!(store.selected().expect("selected failed"), Some(home));
+    }
+
+    /// Unknown, duplicate or unsafe names are rejected.
+    #[test]
+    fn store_rejects_invalid_names() {
+        // Arrange
+        let dir = TempDir::new();
+        let store = IdentityStore::new(&dir.0);
+        store.generate("alice").expect("generate failed");
+
+        // Act
+        let unknown = store.select("bob");
+        let duplicate = store.generate("alice");
+        let traversal = store.generate("../escape");
+
+        // Assert
+        assert!(matches!(unknown, Err(TransportError::InvalidConfig(_))));
+        assert!(matches!(duplicate, Err(TransportError::InvalidConfig(_))));
+        assert!(matches!(traversal, Err(TransportError::InvalidConfig(_))));
+    }
+
+    /// Identities exported to PKCS#12 import back under a new name with the same certificate.
+    #[test]
+    fn pkcs12_export_and_import_round_trip() {
+        // Arrange
+        let dir = TempDir::new();
+        let store = IdentityStore::new(&dir.0);
+        let original = store.generate("alice").expect("generate failed");
+
+        // Act
+        let der = store
+            .export_pkcs12("alice", Some("backup"))
+            .expect("export failed");
+        let imported = store
+            .import_pkcs12("restored", &der, Some("backup"))
+            .expect("import failed");
+
+        // Assert
+        let certificate_der = |pem: &str| {
+            ClientIdentity::from_pem(pem.as_bytes(), None)
+                .expect("load failed")
+                .certificate()
+                .to_der()
+                .expect("der failed")
+        };
+        assert_eq!(
+            certificate_der(imported.cert_pem.expose()),
+            certificate_der(original.cert_pem.expose())
+        );
+        assert_eq!(
+            store.list().expect("list failed"),
+            vec!["alice", "restored"]
+        );
+    }
+
+    /// Imports with the wrong password or onto an existing name are rejected.
+    #[test]
+    fn import_pkcs12_rejects_bad_input() {
+        // Arrange
+        let dir = TempDir::new();
+        let store = IdentityStore::new(&dir.0);
+        store.generate("alice").expect("generate failed");
+        let der = store
+            .export_pkcs12("alice", Some("pw"))
+            .expect("export failed");
+
+        // Act
+        let wrong_password = store.import_pkcs12("bob", &der, Some("nope"));
+        let duplicate = store.import_pkcs12("alice", &der, Some("pw"));
+
+        // Assert
+        assert!(matches!(
+            wrong_password,
+            Err(TransportError::InvalidConfig(_))
+        ));
+        assert!(matches!(duplicate, Err(TransportError::InvalidConfig(_))));
+        assert_eq!(store.list().expect("list failed"), vec!["alice"]);
+    }
+
+    /// Applying an identity replaces any other certificate source in the config.
+    #[test]
+    fn apply_to_sets_config_certificate() {
+        // Arrange
+        let dir = TempDir::new();
+        let identity = IdentityStore::new(&dir.0)
+            .generate("alice")
+            .expect("generate failed");
+        let mut config = MumbleConfig::new("example.org".to_string(), 64738, "alice".to_string());
+        config.cert_pkcs12 = Some(vec![1, 2, 3].into());
+        config.cert_password = Some("pw".into());
+
+        // Act
+        identity.apply_to(&mut config);
+
+        // Assert
+        assert_eq!(config.cert_pem, Some(identity.cert_pem));
+        assert!(config.cert_pkcs12.is_none());
+        assert!(config.cert_password.is_none());
+    }
+}
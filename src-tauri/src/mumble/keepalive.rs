@@ -0,0 +1,229 @@
+use crate::transport::errors::TransportError;
+use mumble_protocol_2x::control::msgs;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(5);
+pub const DEFAULT_PING_TIMEOUT: Duration = Duration::from_secs(20);
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_PING_INTERVAL,
+            timeout: DEFAULT_PING_TIMEOUT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PingStats {
+    pub good: u32,
+    pub late: u32,
+    pub lost: u32,
+    pub resync: u32,
+    pub udp_packets: u32,
+    pub tcp_packets: u32,
+}
+
+#[derive(Debug)]
+pub struct PingScheduler {
+    config: KeepaliveConfig,
+    epoch: Instant,
+    next_ping: Instant,
+    last_reply: Instant,
+    stats: PingStats,
+    rtt_count: u32,
+    rtt_mean: f64,
+    rtt_m2: f64,
+}
+
+impl PingScheduler {
+    pub fn new(config: KeepaliveConfig, now: Instant) -> Self {
+        Self {
+            config,
+            epoch: now,
+            next_ping: now,
+            last_reply: now,
+            stats: PingStats::default(),
+            rtt_count: 0,
+            rtt_mean: 0.0,
+            rtt_m2: 0.0,
+        }
+    }
+
+    pub fn stats_mut(&mut self) -> &mut PingStats {
+        &mut self.stats
+    }
+
+    pub fn record_tcp_packet(&mut self) {
+        self.stats.tcp_packets = self.stats.tcp_packets.wrapping_add(1);
+    }
+
+    pub fn poll(&mut self, now: Instant) -> Result<Option<msgs::Ping>, TransportError> {
+        if now.duration_since(self.last_reply) >= self.config.timeout {
+            return Err(TransportError::Timeout);
+        }
+        if now < self.next_ping {
+            return Ok(None);
+        }
+        self.next_ping = now + self.config.interval;
+
+        let mut ping = msgs::Ping::new();
+        ping.timestamp = Some(now.duration_since(self.epoch).as_micros() as u64);
+        ping.good = Some(self.stats.good);
+        ping.late = Some(self.stats.late);
+        ping.lost = Some(self.stats.lost);
+        ping.resync = Some(self.stats.resync);
+        ping.udp_packets = Some(self.stats.udp_packets);
+        ping.tcp_packets = Some(self.stats.tcp_packets);
+        if self.rtt_count > 0 {
+            ping.tcp_ping_avg = Some(self.rtt_mean as f32);
+            ping.tcp_ping_var = Some(self.rtt_variance() as f32);
+        }
+        Ok(Some(ping))
+    }
+
+    pub fn on_pong(&mut self, pong: &msgs::Ping, now: Instant) -> Option<Duration> {
+        let sent_at = self.epoch + Duration::from_micros(pong.timestamp?);
+        let rtt = now.checked_duration_since(sent_at)?;
+        self.last_reply = now;
+
+        // Welford's running mean/variance, reported back to the server in milliseconds.
+        let sample = rtt.as_secs_f64() * 1000.0;
+        self.rtt_count += 1;
+        let delta = sample - self.rtt_mean;
+        self.rtt_mean += delta / f64::from(self.rtt_count);
+        self.rtt_m2 += delta * (sample - self.rtt_mean);
+        Some(rtt)
+    }
+
+    fn rtt_variance(&self) -> f64 {
+        if self.rtt_count < 2 {
+            0.0
+        } else {
+            self.rtt_m2 / f64::from(self.rtt_count - 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeepaliveConfig, PingScheduler};
+    use crate::transport::errors::TransportError;
+    use std::time::{Duration, Instant};
+
+    fn config() -> KeepaliveConfig {
+        KeepaliveConfig {
+            interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(20),
+        }
+    }
+
+    /// The first poll sends a ping and later polls wait for the interval.
+    #[test]
+    fn poll_sends_on_interval() {
+        // Arrange
+        let start = Instant::now();
+        let mut scheduler = PingScheduler::new(config(), start);
+
+        // Act
+        let first = scheduler.poll(start).expect("poll failed");
+        let early = scheduler
+            .poll(start + Duration::from_secs(1))
+            .expect("poll failed");
+        let due = scheduler
+            .poll(start + Duration::from_secs(5))
+            .expect("poll failed");
+
+        // Assert
+        assert_eq!(first.expect("missing ping").timestamp, Some(0));
+        assert!(early.is_none());
+        assert_eq!(due.expect("missing ping").timestamp, Some(5_000_000));
+    }
+
+    /// Ping packets carry the packet statistics gathered so far.
+    #[test]
+    fn poll_includes_packet_statistics() {
+        // Arrange
+        let start = Instant::now();
+        let mut scheduler = PingScheduler::new(config(), start);
+        scheduler.record_tcp_packet();
+        scheduler.record_tcp_packet();
+        scheduler.stats_mut().lost = 3;
+
+        // Act
+        let ping = scheduler
+            .poll(start)
+            .expect("poll failed")
+            .expect("no ping");
+
+        // Assert
+        assert_eq!(ping.tcp_packets, Some(2));
+        assert_eq!(ping.lost, Some(3));
+        assert_eq!(ping.tcp_ping_avg, None);
+    }
+
+    /// Replies are turned into round-trip times and fold into the running average.
+    #[test]
+    fn on_pong_measures_round_trip_time() {
+        // Arrange
+        let start = Instant::now();
+        let mut scheduler = PingScheduler::new(config(), start);
+        let ping = scheduler
+            .poll(start)
+            .expect("poll failed")
+            .expect("no ping");
+
+        // Act
+        let rtt = scheduler.on_pong(&ping, start + Duration::from_millis(40));
+        let next = scheduler
+            .poll(start + Duration::from_secs(5))
+            .expect("poll failed")
+            .expect("no ping");
+
+        // Assert
+        assert_eq!(rtt, Some(Duration::from_millis(40)));
+        assert_eq!(next.tcp_ping_avg, Some(40.0));
+        assert_eq!(next.tcp_ping_var, Some(0.0));
+    }
+
+    /// Replies without a timestamp are ignored.
+    #[test]
+    fn on_pong_ignores_missing_timestamp() {
+        // Arrange
+        let start = Instant::now();
+        let mut scheduler = PingScheduler::new(config(), start);
+
+        // Act
+        let rtt = scheduler.on_pong(&mumble_protocol_2x::control::msgs::Ping::new(), start);
+
+        // Assert
+        assert!(rtt.is_none());
+    }
+
+    /// Missing replies past the deadline report a timeout.
+    #[test]
+    fn poll_times_out_without_replies() {
+        // Arrange
+        let start = Instant::now();
+        let mut scheduler = PingScheduler::new(config(), start);
+        let ping = scheduler
+            .poll(start)
+            .expect("poll failed")
+            .expect("no ping");
+        scheduler.on_pong(&ping, start + Duration::from_secs(1));
+
+        // Act
+        let alive = scheduler.poll(start + Duration::from_secs(20));
+        let dead = scheduler.poll(start + Duration::from_secs(21));
+
+        // Assert
+        assert!(alive.is_ok());
+        assert!(matches!(dead, Err(TransportError::Timeout)));
+    }
+}
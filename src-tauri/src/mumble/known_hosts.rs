@@ -0,0 +1,235 @@
+use crate::mumble::config::MumbleConfig;
+use crate::mumble::vault::write_private;
+use crate::transport::errors::TransportError;
+use crate::transport::types::UntrustedCertificate;
+use openssl::hash::MessageDigest;
+use openssl::x509::X509Ref;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const KNOWN_HOSTS_FILE: &str = "known_hosts";
+
+#[derive(Clone, Debug)]
+pub struct KnownHosts {
+    path: PathBuf,
+    entries: BTreeMap<String, String>,
+}
+
+impl KnownHosts {
+    pub fn load(app_data_dir: &Path) -> Result<Self, TransportError> {
+        let path = app_data_dir.join(KNOWN_HOSTS_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut entries = BTreeMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once(' ') {
+                Some((key, fingerprint)) => {
+                    entries.insert(key.to_string(), fingerprint.trim().to_string());
+                }
+                None => {
+                    return Err(TransportError::InvalidConfig(format!(
+                        "malformed known_hosts entry: {line}"
+                    )));
+                }
+            }
+        }
+        Ok(Self { path, entries })
+    }
+
+    pub fn fingerprint(&self, host: &str, port: u16) -> Option<&str> {
+        self.entries.get(&host_key(host, port)).map(String::as_str)
+    }
+
+    pub fn trust(
+        &mut self,
+        host: &str,
+        port: u16,
+        fingerprint: &str,
+    ) -> Result<(), TransportError> {
+        self.entries
+            .insert(host_key(host, port), fingerprint.to_string());
+        self.save()
+    }
+
+    pub fn forget(&mut self, host: &str, port: u16) -> Result<(), TransportError> {
+        if self.entries.remove(&host_key(host, port)).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn apply_to(&self, config: &mut MumbleConfig) {
+        config.trusted_fingerprint = self
+            .fingerprint(&config.server, config.port)
+            .map(str::to_string);
+    }
+
+    fn save(&self) -> Result<(), TransportError> {
+        let mut contents = String::new();
+        for (key, fingerprint) in &self.entries {
+            contents.push_str(&format!("{key} {fingerprint}\n"));
+        }
+        write_private(&self.path, contents.as_bytes())
+    }
+}
+
+pub fn certificate_fingerprint(certificate: &X509Ref) -> Result<String, TransportError> {
+    let digest = certificate
+        .digest(MessageDigest::sha256())
+        .map_err(|err| TransportError::Protocol(format!("certificate digest failed: {err}")))?;
+    Ok(digest
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
+
+pub fn verify_server_certificate(
+    host: &str,
+    port: u16,
+    pinned: Option<&str>,
+    fingerprint: &str,
+    chain_verified: bool,
+) -> Result<(), TransportError> {
+    // A pin always wins; without one, only certificates that chain to a trusted CA are accepted.
+    let trusted = match pinned {
+        Some(pinned) => pinned.eq_ignore_ascii_case(fingerprint),
+        None => chain_verified,
+    };
+    if trusted {
+        return Ok(());
+    }
+    Err(TransportError::UntrustedCertificate(UntrustedCertificate {
+        host: host.to_string(),
+        port,
+        fingerprint: fingerprint.to_string(),
+        pinned: pinned.map(str::to_string),
+    }))
+}
+
+pub(crate) fn host_key(host: &str, port: u16) -> String {
+    let host = host.trim().to_ascii_lowercase();
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{certificate_fingerprint, verify_server_certificate, KnownHosts};
+    use crate::mumble::certificate::tests::self_signed;
+    use crate::mumble::config::MumbleConfig;
+    use crate::mumble::identity::tests::TempDir;
+    use crate::transport::errors::TransportError;
+
+    /// Fingerprints are colon-separated uppercase SHA-256 hex.
+    #[test]
+    fn certificate_fingerprint_formats_sha256() {
+        // Arrange
+        let (certificate, _) = self_signed("server");
+
+        // Act
+        let fingerprint = certificate_fingerprint(&certificate).expect("fingerprint failed");
+
+        // Assert
+        assert_eq!(fingerprint.len(), 32 * 3 - 1);
+        assert!(fingerprint
+            .split(':')
+            .all(|byte| byte.len() == 2 && byte == byte.to_ascii_uppercase()));
+    }
+
+    /// Trusted fingerprints persist across loads and are keyed by host and port.
+    #[test]
+    fn trust_persists_per_host_and_port() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut hosts = KnownHosts::load(&dir.0).expect("load failed");
+
+        // Act
+        hosts
+            .trust("Voice.Example", 64738, "AA:BB")
+            .expect("trust failed");
+        hosts.trust("::1", 64738, "CC:DD").expect("trust failed");
+        let reloaded = KnownHosts::load(&dir.0).expect("reload failed");
+
+        // Assert
+        assert_eq!(reloaded.fingerprint("voice.example", 64738), Some("AA:BB"));
+        assert_eq!(reloaded.fingerprint("voice.example", 64739), None);
+        assert_eq!(reloaded.fingerprint("::1", 64738), Some("CC:DD"));
+    }
+
+    /// Forgetting a host removes its pin and applying fills the config.
+    #[test]
+    fn forget_and_apply_update_config() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut hosts = KnownHosts::load(&dir.0).expect("load failed");
+        hosts
+            .trust("voice.example", 64738, "AA:BB")
+            .expect("trust failed");
+        let mut config = MumbleConfig::new("voice.example".to_string(), 64738, "alice".to_string());
+
+        // Act
+        hosts.apply_to(&mut config);
+        let pinned = config.trusted_fingerprint.clone();
+        hosts.forget("voice.example", 64738).expect("forget failed");
+        hosts.apply_to(&mut config);
+
+        // Assert
+        assert_eq!(pinned, Some("AA:BB".to_string()));
+        assert!(config.trusted_fingerprint.is_none());
+        assert!(KnownHosts::load(&dir.0)
+            .expect("reload failed")
+            .fingerprint("voice.example", 64738)
+            .is_none());
+    }
+
+    /// Unknown self-signed certificates and changed pins are reported as untrusted.
+    #[test]
+    fn verify_server_certificate_applies_tofu_rules() {
+        // Arrange
+        // Act
+        let pinned = verify_server_certificate("host", 1, Some("aa:bb"), "AA:BB", false);
+        let ca_signed = verify_server_certificate("host", 1, None, "AA:BB", true);
+        let unknown = verify_server_certificate("host", 1, None, "AA:BB", false);
+        let changed = verify_server_certificate("host", 1, Some("CC:DD"), "AA:BB", true);
+
+        // Assert
+        assert!(pinned.is_ok());
+        assert!(ca_signed.is_ok());
+        assert!(matches!(
+            unknown,
+            Err(TransportError::UntrustedCertificate(ref certificate)) if certificate.pinned.is_none()
+        ));
+        assert!(matches!(
+            changed,
+            Err(TransportError::UntrustedCertificate(ref certificate))
+                if certificate.pinned.as_deref() == Some("CC:DD")
+        ));
+    }
+
+    /// Malformed files are reported instead of silently dropping pins.
+    #[test]
+    fn load_rejects_malformed_entries() {
+        // Arrange
+        let dir = TempDir::new();
+        std::fs::write(dir.0.join("known_hosts"), "# comment\nbroken\n").expect("write failed");
+
+        // Act
+        let result = KnownHosts::load(&dir.0);
+
+        // Assert
+        assert!(matches!(result, Err(TransportError::InvalidConfig(_))));
+    }
+}
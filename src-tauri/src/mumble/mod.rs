@@ -1,16 +1,47 @@
+pub mod audio;
+pub mod bookmarks;
+pub mod certificate;
 pub mod config;
+pub mod connect;
 pub mod control;
+pub mod crypt;
+pub mod diagnostics;
 pub mod events;
+pub mod identity;
+pub mod keepalive;
+pub mod known_hosts;
+pub mod proxy;
+pub mod secret;
 pub mod state;
+pub mod tls;
 pub mod transport;
+pub mod vault;
+pub mod voice;
 
+pub use audio::{DecodedAudio, FrameDuration, VoiceDecoder, VoiceEncoder};
+pub use bookmarks::{Bookmark, BookmarkStore};
+pub use certificate::{ClientCertificate, ClientIdentity};
 pub use config::MumbleConfig;
+pub use connect::ConnectTimeouts;
 #[cfg(not(feature = "coverage"))]
 pub use control::tls_connect;
 pub use control::{
     BlockingControlTransport, ControlConnector, ControlHandshake, ControlMessage, ControlSession,
-    ControlTransport, HandshakeRequest, MumbleProtocolControlConnector, NoopControlConnector,
-    SocketControlConnector, UserStateCommand,
+    ControlTransport, HandshakeRequest, MumbleProtocolControlConnector,
+    MumbleProtocolControlSession, NoopControlConnector, ShutdownStream, SocketControlConnector,
+    TextMessageCommand, UserStateCommand, CLIENT_RELEASE, CLIENT_VERSION,
 };
-pub use events::{TextMessage, TransportEvent};
-pub use transport::MumbleTransport;
+pub use crypt::{CryptStats, VoiceCrypt};
+pub use diagnostics::{
+    run_diagnostics, DiagnosticReport, DiagnosticStage, ServerPing, StageResult, StageStatus,
+};
+pub use events::{RemovalKind, RemovalNotice, TextMessage, TransportEvent};
+pub use identity::{Identity, IdentityStore};
+pub use keepalive::{KeepaliveConfig, PingScheduler};
+pub use known_hosts::KnownHosts;
+pub use proxy::{ProxyConfig, ProxyKind, ProxyStore};
+pub use secret::{SecretBytes, SecretString};
+pub use tls::{CertificateInfo, TlsPolicy, TlsSessionInfo, TlsVersion};
+pub use transport::{MumbleTransport, TextTarget};
+pub use vault::{CredentialVault, Credentials, VaultKey};
+pub use voice::{VoiceChannel, VoicePath};
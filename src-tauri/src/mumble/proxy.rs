@@ -0,0 +1,798 @@
+use crate::mumble::config::MumbleConfig;
+use crate::mumble::connect::{
+    connect_happy_eyeballs, resolve, strip_brackets, ConnectTimeouts, CONNECTION_ATTEMPT_DELAY,
+};
+use crate::mumble::known_hosts::host_key;
+use crate::mumble::secret::{SecretBytes, SecretString};
+use crate::mumble::vault::{write_private, CredentialVault};
+use crate::transport::errors::{ConnectPhase, TransportError};
+use openssl::base64;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::path::{Path, PathBuf};
+
+const PROXY_FILE: &str = "proxy.json";
+const SOCKS_VERSION: u8 = 5;
+const SOCKS_AUTH_VERSION: u8 = 1;
+const SOCKS_NO_AUTH: u8 = 0x00;
+const SOCKS_USER_PASS: u8 = 0x02;
+const SOCKS_NO_ACCEPTABLE: u8 = 0xFF;
+const SOCKS_CONNECT: u8 = 0x01;
+const SOCKS_ATYP_IPV4: u8 = 0x01;
+const SOCKS_ATYP_DOMAIN: u8 = 0x03;
+const SOCKS_ATYP_IPV6: u8 = 0x04;
+const MAX_HTTP_RESPONSE: usize = 8 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProxyKind {
+    Socks5,
+    HttpConnect,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    pub kind: ProxyKind,
+    pub server: String,
+    pub port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    // Kept in the credential vault under the proxy endpoint, never in proxy.json.
+    #[serde(skip)]
+    pub password: Option<SecretString>,
+}
+
+impl ProxyConfig {
+    pub fn connect(
+        &self,
+        target: &str,
+        port: u16,
+        timeouts: ConnectTimeouts,
+    ) -> Result<TcpStream, TransportError> {
+        let addresses = resolve(&self.server, self.port)?;
+        let mut stream =
+            connect_happy_eyeballs(&addresses, timeouts.connect, CONNECTION_ATTEMPT_DELAY)?;
+        stream.set_read_timeout(Some(timeouts.handshake))?;
+        stream.set_write_timeout(Some(timeouts.handshake))?;
+        self.negotiate(&mut stream, target, port)?;
+        Ok(stream)
+    }
+
+    pub fn negotiate<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        target: &str,
+        port: u16,
+    ) -> Result<(), TransportError> {
+        match self.kind {
+            ProxyKind::Socks5 => self.socks5_connect(stream, target, port),
+            ProxyKind::HttpConnect => self.http_connect(stream, target, port),
+        }
+    }
+
+    fn password(&self) -> &str {
+        self.password
+            .as_ref()
+            .map(SecretString::expose)
+            .unwrap_or("")
+    }
+
+    fn socks5_connect<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        target: &str,
+        port: u16,
+    ) -> Result<(), TransportError> {
+        let greeting: &[u8] = match self.username {
+            Some(_) => &[SOCKS_VERSION, 2, SOCKS_NO_AUTH, SOCKS_USER_PASS],
+            None => &[SOCKS_VERSION, 1, SOCKS_NO_AUTH],
+        };
+        stream.write_all(greeting).map_err(proxy_io)?;
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice).map_err(proxy_io)?;
+        if choice[0] != SOCKS_VERSION {
+            return Err(proxy_error("proxy is not a SOCKS5 server".to_string()));
+        }
+        match (choice[1], &self.username) {
+            (SOCKS_NO_AUTH, _) => {}
+            (SOCKS_USER_PASS, Some(username)) => {
+                self.socks5_authenticate(stream, username)?;
+            }
+            (SOCKS_NO_ACCEPTABLE, _) => {
+                return Err(proxy_error(
+                    "proxy accepts none of the offered authentication methods".to_string(),
+                ));
+            }
+            (method, _) => {
+                return Err(proxy_error(format!(
+                    "proxy chose unsupported authentication method {method:#04x}"
+                )));
+            }
+        }
+
+        let mut request = vec![SOCKS_VERSION, SOCKS_CONNECT, 0];
+        let host = strip_brackets(target);
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(address)) => {
+                request.push(SOCKS_ATYP_IPV4);
+                request.extend(address.octets());
+            }
+            Ok(IpAddr::V6(address)) => {
+                request.push(SOCKS_ATYP_IPV6);
+                request.extend(address.octets());
+            }
+            Err(_) => {
+                // Let the proxy resolve names so lookups also work where local DNS is filtered.
+                let name = u8::try_from(host.len()).map_err(|_| {
+                    TransportError::InvalidConfig(format!("server name too long: {host}"))
+                })?;
+                request.push(SOCKS_ATYP_DOMAIN);
+                request.push(name);
+                request.extend(host.as_bytes());
+            }
+        }
+        request.extend(port.to_be_bytes());
+        stream.write_all(&request).map_err(proxy_io)?;
+
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).map_err(proxy_io)?;
+        if reply[1] != 0 {
+            return Err(proxy_error(socks5_reply_message(reply[1])));
+        }
+        let bound_len = match reply[3] {
+            SOCKS_ATYP_IPV4 => 4,
+            SOCKS_ATYP_IPV6 => 16,
+            SOCKS_ATYP_DOMAIN => {
+                let mut len = [0u8; 1];
+                stream.read_exact(&mut len).map_err(proxy_io)?;
+                usize::from(len[0])
+            }
+            other => {
+                return Err(proxy_error(format!(
+                    "proxy replied with unknown address type {other:#04x}"
+                )));
+            }
+        };
+        let mut bound = vec![0u8; bound_len + 2];
+        stream.read_exact(&mut bound).map_err(proxy_io)?;
+        Ok(())
+    }
+
+    fn socks5_authenticate<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        username: &str,
+    ) -> Result<(), TransportError> {
+        let password = self.password();
+        let (Ok(username_len), Ok(password_len)) =
+            (u8::try_from(username.len()), u8::try_from(password.len()))
+        else {
+            return Err(TransportError::InvalidConfig(
+                "SOCKS5 username and password must be at most 255 bytes".to_string(),
+            ));
+        };
+        let mut request = Vec::with_capacity(3 + username.len() + password.len());
+        request.extend([SOCKS_AUTH_VERSION, username_len]);
+        request.extend(username.as_bytes());
+        request.push(password_len);
+        request.extend(password.as_bytes());
+        let request = SecretBytes::from(request);
+        stream.write_all(request.expose()).map_err(proxy_io)?;
+
+        let mut status = [0u8; 2];
+        stream.read_exact(&mut status).map_err(proxy_io)?;
+        if status[1] != 0 {
+            return Err(proxy_error("proxy rejected the credentials".to_string()));
+        }
+        Ok(())
+    }
+
+    fn http_connect<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        target: &str,
+        port: u16,
+    ) -> Result<(), TransportError> {
+        let authority = authority(target, port);
+        // Reserve room up front so appending credentials normally does not reallocate and
+        // leave an unzeroed copy behind.
+        let mut request = String::with_capacity(1024);
+        request.push_str(&format!(
+            "CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n"
+        ));
+        if let Some(username) = &self.username {
+            let credentials = SecretString::from(format!("{username}:{}", self.password()));
+            let encoded = SecretString::from(base64::encode_block(credentials.expose().as_bytes()));
+            request.push_str("Proxy-Authorization: Basic ");
+            request.push_str(encoded.expose());
+            request.push_str("\r\n");
+        }
+        request.push_str("\r\n");
+        let request = SecretString::from(request);
+        stream
+            .write_all(request.expose().as_bytes())
+            .map_err(proxy_io)?;
+
+        // Read byte by byte so no TLS data that follows the headers is consumed here.
+        let mut response = Vec::new();
+        let mut byte = [0u8; 1];
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_HTTP_RESPONSE {
+                return Err(proxy_error("proxy response headers too long".to_string()));
+            }
+            if stream.read(&mut byte).map_err(proxy_io)? == 0 {
+                return Err(proxy_error(
+                    "proxy closed the connection during CONNECT".to_string(),
+                ));
+            }
+            response.push(byte[0]);
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+        let mut parts = status_line.splitn(2, ' ');
+        let version = parts.next().unwrap_or_default();
+        let status = parts.next().unwrap_or_default().trim();
+        let code = status
+            .split(' ')
+            .next()
+            .and_then(|code| code.parse::<u16>().ok());
+        match code {
+            Some(200..=299) if version.starts_with("HTTP/1.") => Ok(()),
+            Some(_) => Err(proxy_error(status.to_string())),
+            None => Err(proxy_error(format!(
+                "malformed proxy response: {status_line}"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct ProxySettings {
+    #[serde(default)]
+    global: Option<ProxyConfig>,
+    #[serde(default)]
+    servers: BTreeMap<String, ProxyConfig>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProxyStore {
+    path: PathBuf,
+    settings: ProxySettings,
+}
+
+impl ProxyStore {
+    pub fn load(app_data_dir: &Path) -> Result<Self, TransportError> {
+        let path = app_data_dir.join(PROXY_FILE);
+        let settings = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents).map_err(|err| {
+                TransportError::InvalidConfig(format!("invalid proxy settings: {err}"))
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => ProxySettings::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self { path, settings })
+    }
+
+    pub fn global(&self) -> Option<&ProxyConfig> {
+        self.settings.global.as_ref()
+    }
+
+    pub fn set_global(
+        &mut self,
+        proxy: Option<ProxyConfig>,
+        mut vault: Option<&mut CredentialVault>,
+    ) -> Result<(), TransportError> {
+        if let Some(proxy) = &proxy {
+            store_password(proxy, vault.as_deref_mut())?;
+        }
+        let previous = std::mem::replace(&mut self.settings.global, proxy);
+        self.save()?;
+        self.release_password(previous, vault)
+    }
+
+    pub fn server_proxy(&self, host: &str, port: u16) -> Option<&ProxyConfig> {
+        self.settings.servers.get(&host_key(host, port))
+    }
+
+    pub fn set_server_proxy(
+        &mut self,
+        host: &str,
+        port: u16,
+        proxy: Option<ProxyConfig>,
+        mut vault: Option<&mut CredentialVault>,
+    ) -> Result<(), TransportError> {
+        if let Some(proxy) = &proxy {
+            store_password(proxy, vault.as_deref_mut())?;
+        }
+        let key = host_key(host, port);
+        let previous = match proxy {
+            Some(proxy) => self.settings.servers.insert(key, proxy),
+            None => self.settings.servers.remove(&key),
+        };
+        self.save()?;
+        self.release_password(previous, vault)
+    }
+
+    pub fn proxy_for(&self, host: &str, port: u16) -> Option<&ProxyConfig> {
+        self.server_proxy(host, port)
+            .or(self.settings.global.as_ref())
+    }
+
+    pub fn apply_to(&self, config: &mut MumbleConfig, vault: Option<&CredentialVault>) {
+        config.proxy = self.proxy_for(&config.server, config.port).map(|proxy| {
+            let mut proxy = proxy.clone();
+            proxy.password = vault
+                .filter(|_| proxy.username.is_some())
+                .and_then(|vault| vault.proxy_password(&proxy.server, proxy.port))
+                .cloned();
+            proxy
+        });
+    }
+
+    fn release_password(
+        &self,
+        previous: Option<ProxyConfig>,
+        vault: Option<&mut CredentialVault>,
+    ) -> Result<(), TransportError> {
+        let (Some(previous), Some(vault)) = (previous, vault) else {
+            return Ok(());
+        };
+        // Several entries can share one proxy, so its password goes only with the last of them.
+        let endpoint = host_key(&previous.server, previous.port);
+        let in_use = self
+            .settings
+            .global
+            .iter()
+            .chain(self.settings.servers.values())
+            .any(|proxy| host_key(&proxy.server, proxy.port) == endpoint);
+        if in_use {
+            return Ok(());
+        }
+        vault.clear_proxy_password(&previous.server, previous.port)
+    }
+
+    fn save(&self) -> Result<(), TransportError> {
+        let contents = serde_json::to_vec_pretty(&self.settings)
+            .map_err(|err| TransportError::Io(format!("proxy settings encoding failed: {err}")))?;
+        write_private(&self.path, &contents)
+    }
+}
+
+fn store_password(
+    proxy: &ProxyConfig,
+    vault: Option<&mut CredentialVault>,
+) -> Result<(), TransportError> {
+    // Settings read back from disk carry no password; saving them keeps the stored one.
+    match (vault, &proxy.password) {
+        (Some(vault), Some(password)) => {
+            vault.set_proxy_password(&proxy.server, proxy.port, password.clone())
+        }
+        (None, Some(_)) => Err(TransportError::InvalidConfig(
+            "proxy passwords can only be saved in the credential vault".to_string(),
+        )),
+        (_, None) => Ok(()),
+    }
+}
+
+fn authority(host: &str, port: u16) -> String {
+    let host = strip_brackets(host);
+    if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+fn socks5_reply_message(reply: u8) -> String {
+    match reply {
+        0x01 => "general SOCKS server failure".to_string(),
+        0x02 => "connection not allowed by ruleset".to_string(),
+        0x03 => "network unreachable".to_string(),
+        0x04 => "host unreachable".to_string(),
+        0x05 => "connection refused".to_string(),
+        0x06 => "TTL expired".to_string(),
+        0x07 => "command not supported".to_string(),
+        0x08 => "address type not supported".to_string(),
+        other => format!("unknown SOCKS5 reply {other:#04x}"),
+    }
+}
+
+fn proxy_io(error: std::io::Error) -> TransportError {
+    proxy_error(error.to_string())
+}
+
+fn proxy_error(message: String) -> TransportError {
+    TransportError::Connect {
+        phase: ConnectPhase::Proxy,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{authority, ProxyConfig, ProxyKind, ProxyStore};
+    use crate::mumble::config::MumbleConfig;
+    use crate::mumble::connect::ConnectTimeouts;
+    use crate::mumble::identity::tests::TempDir;
+    use crate::mumble::vault::{CredentialVault, VaultKey};
+    use crate::transport::errors::{ConnectPhase, TransportError};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread::JoinHandle;
+
+    fn stand_in<T, F>(script: F) -> (u16, JoinHandle<T>)
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut TcpStream) -> T + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind failed");
+        let port = listener.local_addr().expect("missing address").port();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept failed");
+            script(&mut stream)
+        });
+        (port, handle)
+    }
+
+    fn read_bytes(stream: &mut TcpStream, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        stream.read_exact(&mut bytes).expect("read failed");
+        bytes
+    }
+
+    fn read_headers(stream: &mut TcpStream) -> String {
+        let mut headers = Vec::new();
+        while !headers.ends_with(b"\r\n\r\n") {
+            headers.extend(read_bytes(stream, 1));
+        }
+        String::from_utf8(headers).expect("headers are not utf-8")
+    }
+
+    fn echo(stream: &mut TcpStream) {
+        let bytes = read_bytes(stream, 4);
+        stream.write_all(&bytes).expect("echo failed");
+    }
+
+    fn proxy(kind: ProxyKind, port: u16) -> ProxyConfig {
+        ProxyConfig {
+            kind,
+            server: "127.0.0.1".to_string(),
+            port,
+            username: None,
+            password: None,
+        }
+    }
+
+    fn round_trip(stream: &mut TcpStream) -> Vec<u8> {
+        stream.write_all(b"ping").expect("write failed");
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).expect("read failed");
+        reply.to_vec()
+    }
+
+    /// SOCKS5 without credentials asks the proxy to resolve the server name.
+    #[test]
+    fn socks5_connects_by_name() {
+        // Arrange
+        let (port, proxy_side) = stand_in(|stream| {
+            let greeting = read_bytes(stream, 3);
+            stream.write_all(&[5, 0]).expect("write failed");
+            let mut request = read_bytes(stream, 5);
+            let name_len = usize::from(request[4]);
+            request.extend(read_bytes(stream, name_len + 2));
+            stream
+                .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0x1F, 0x90])
+                .expect("write failed");
+            echo(stream);
+            (greeting, request)
+        });
+
+        // Act
+        let mut stream = proxy(ProxyKind::Socks5, port)
+            .connect("voice.example", 64738, ConnectTimeouts::default())
+            .expect("proxy connect failed");
+        let reply = round_trip(&mut stream);
+        let (greeting, request) = proxy_side.join().expect("proxy panicked");
+
+        // Assert
+        assert_eq!(greeting, [5, 1, 0]);
+        let mut expected = vec![5, 1, 0, 3, 13];
+        expected.extend(b"voice.example");
+        expected.extend(64738u16.to_be_bytes());
+        assert_eq!(request, expected);
+        assert_eq!(reply, b"ping");
+    }
+
+    /// SOCKS5 credentials use username/password auth and IP targets are sent as addresses.
+    #[test]
+    fn socks5_authenticates_with_credentials() {
+        // Arrange
+        let (port, proxy_side) = stand_in(|stream| {
+            let greeting = read_bytes(stream, 4);
+            stream.write_all(&[5, 2]).expect("write failed");
+            let mut auth = read_bytes(stream, 2);
+            auth.extend(read_bytes(stream, usize::from(auth[1]) + 1));
+            let password_len = usize::from(*auth.last().expect("missing length"));
+            auth.extend(read_bytes(stream, password_len));
+            stream.write_all(&[1, 0]).expect("write failed");
+            let request = read_bytes(stream, 10);
+            let mut reply = vec![5, 0, 0, 4];
+            reply.extend([0u8; 18]);
+            stream.write_all(&reply).expect("write failed");
+            echo(stream);
+            (greeting, auth, request)
+        });
+        let mut config = proxy(ProxyKind::Socks5, port);
+        config.username = Some("alice".to_string());
+        config.password = Some("pw".into());
+
+        // Act
+        let mut stream = config
+            .connect("10.0.0.1", 64738, ConnectTimeouts::default())
+            .expect("proxy connect failed");
+        let reply = round_trip(&mut stream);
+        let (greeting, auth, request) = proxy_side.join().expect("proxy panicked");
+
+        // Assert
+        assert_eq!(greeting, [5, 2, 0, 2]);
+        assert_eq!(auth, b"\x01\x05alice\x02pw");
+        assert_eq!(request, [5, 1, 0, 1, 10, 0, 0, 1, 0xFC, 0xE2]);
+        assert_eq!(reply, b"ping");
+    }
+
+    /// Rejected credentials and refused targets fail in the proxy phase with a reason.
+    #[test]
+    fn socks5_reports_failures() {
+        // Arrange
+        let (bad_auth_port, bad_auth) = stand_in(|stream| {
+            read_bytes(stream, 4);
+            stream.write_all(&[5, 2]).expect("write failed");
+            read_bytes(stream, 10);
+            stream.write_all(&[1, 1]).expect("write failed");
+        });
+        let (refused_port, refused) = stand_in(|stream| {
+            read_bytes(stream, 3);
+            stream.write_all(&[5, 0]).expect("write failed");
+            read_bytes(stream, 10);
+            stream
+                .write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0])
+                .expect("write failed");
+        });
+        let mut with_credentials = proxy(ProxyKind::Socks5, bad_auth_port);
+        with_credentials.username = Some("alice".to_string());
+        with_credentials.password = Some("pw".into());
+
+        // Act
+        let auth_error = with_credentials
+            .connect("10.0.0.1", 64738, ConnectTimeouts::default())
+            .expect_err("expected auth failure");
+        let refused_error = proxy(ProxyKind::Socks5, refused_port)
+            .connect("10.0.0.1", 64738, ConnectTimeouts::default())
+            .expect_err("expected refusal");
+        bad_auth.join().expect("proxy panicked");
+        refused.join().expect("proxy panicked");
+
+        // Assert
+        assert!(matches!(
+            auth_error,
+            TransportError::Connect { phase: ConnectPhase::Proxy, ref message }
+                if message.contains("credentials")
+        ));
+        assert!(matches!(
+            refused_error,
+            TransportError::Connect { phase: ConnectPhase::Proxy, ref message }
+                if message == "connection refused"
+        ));
+    }
+
+    /// HTTP CONNECT sends basic credentials and leaves the tunnel ready for TLS.
+    #[test]
+    fn http_connect_tunnels_with_basic_auth() {
+        // Arrange
+        let (port, proxy_side) = stand_in(|stream| {
+            let headers = read_headers(stream);
+            stream
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .expect("write failed");
+            echo(stream);
+            headers
+        });
+        let mut config = proxy(ProxyKind::HttpConnect, port);
+        config.username = Some("alice".to_string());
+        config.password = Some("pw".into());
+
+        // Act
+        let mut stream = config
+            .connect("voice.example", 64738, ConnectTimeouts::default())
+            .expect("proxy connect failed");
+        let reply = round_trip(&mut stream);
+        let headers = proxy_side.join().expect("proxy panicked");
+
+        // Assert
+        assert!(headers.starts_with("CONNECT voice.example:64738 HTTP/1.1\r\n"));
+        assert!(headers.contains("Host: voice.example:64738\r\n"));
+        assert!(headers.contains("Proxy-Authorization: Basic YWxpY2U6cHc=\r\n"));
+        assert_eq!(reply, b"ping");
+    }
+
+    /// Non-success CONNECT responses surface the proxy's status line.
+    #[test]
+    fn http_connect_reports_error_status() {
+        // Arrange
+        let (port, proxy_side) = stand_in(|stream| {
+            read_headers(stream);
+            stream
+                .write_all(
+                    b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic\r\n\r\n",
+                )
+                .expect("write failed");
+        });
+
+        // Act
+        let err = proxy(ProxyKind::HttpConnect, port)
+            .connect("voice.example", 64738, ConnectTimeouts::default())
+            .expect_err("expected proxy failure");
+        proxy_side.join().expect("proxy panicked");
+
+        // Assert
+        assert_eq!(
+            err.to_string(),
+            "proxy negotiation failed: 407 Proxy Authentication Required"
+        );
+    }
+
+    /// IPv6 targets are bracketed in the CONNECT authority.
+    #[test]
+    fn authority_brackets_ipv6() {
+        // Arrange
+        // Act
+        let v6 = authority("[2001:db8::1]", 64738);
+        let bare_v6 = authority("2001:db8::1", 64738);
+        let name = authority("voice.example", 64738);
+
+        // Assert
+        assert_eq!(v6, "[2001:db8::1]:64738");
+        assert_eq!(bare_v6, "[2001:db8::1]:64738");
+        assert_eq!(name, "voice.example:64738");
+    }
+
+    /// Per-server proxies override the global one and both persist.
+    #[test]
+    fn store_prefers_server_proxy_over_global() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut store = ProxyStore::load(&dir.0).expect("load failed");
+        let mut vault = CredentialVault::open(&dir.0, &VaultKey::Passphrase("secret".into()))
+            .expect("open failed");
+        let global = proxy(ProxyKind::HttpConnect, 3128);
+        let mut server = proxy(ProxyKind::Socks5, 1080);
+        server.username = Some("alice".to_string());
+        server.password = Some("pw".into());
+        let mut config = MumbleConfig::new("voice.example".to_string(), 64738, "alice".to_string());
+        let mut other = MumbleConfig::new("other.example".to_string(), 64738, "alice".to_string());
+
+        // Act
+        store
+            .set_global(Some(global.clone()), Some(&mut vault))
+            .expect("save failed");
+        store
+            .set_server_proxy(
+                "Voice.Example",
+                64738,
+                Some(server.clone()),
+                Some(&mut vault),
+            )
+            .expect("save failed");
+        let reloaded = ProxyStore::load(&dir.0).expect("reload failed");
+        reloaded.apply_to(&mut config, Some(&vault));
+        reloaded.apply_to(&mut other, Some(&vault));
+
+        // Assert
+        assert_eq!(config.proxy, Some(server));
+        assert_eq!(other.proxy, Some(global));
+    }
+
+    /// Proxy passwords live in the vault under the proxy endpoint, never in proxy.json.
+    #[test]
+    fn store_keeps_password_in_vault() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut store = ProxyStore::load(&dir.0).expect("load failed");
+        let mut vault = CredentialVault::open(&dir.0, &VaultKey::Passphrase("secret".into()))
+            .expect("open failed");
+        let mut server = proxy(ProxyKind::Socks5, 1080);
+        server.username = Some("alice".to_string());
+        server.password = Some("hunter2".into());
+        let mut config = MumbleConfig::new("voice.example".to_string(), 64738, "alice".to_string());
+
+        // Act
+        let without_vault = store.set_global(Some(server.clone()), None);
+        store
+            .set_global(Some(server.clone()), Some(&mut vault))
+            .expect("save failed");
+        let reloaded = ProxyStore::load(&dir.0).expect("reload failed");
+        reloaded.apply_to(&mut config, None);
+        let locked = config.proxy.clone();
+        reloaded.apply_to(&mut config, Some(&vault));
+
+        // Assert
+        assert!(matches!(
+            without_vault,
+            Err(TransportError::InvalidConfig(_))
+        ));
+        let raw = std::fs::read_to_string(dir.0.join("proxy.json")).expect("read failed");
+        assert!(!raw.contains("hunter2"));
+        assert!(raw.contains("alice"));
+        assert_eq!(locked.and_then(|proxy| proxy.password), None);
+        assert_eq!(
+            vault.proxy_password("127.0.0.1", 1080),
+            Some(&"hunter2".into())
+        );
+        assert_eq!(config.proxy, Some(server));
+    }
+
+    /// Saving an edited proxy that was read back from disk keeps its stored password.
+    #[test]
+    fn store_keeps_password_when_editing_reloaded_proxy() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut store = ProxyStore::load(&dir.0).expect("load failed");
+        let mut vault = CredentialVault::open(&dir.0, &VaultKey::Passphrase("secret".into()))
+            .expect("open failed");
+        let mut server = proxy(ProxyKind::Socks5, 1080);
+        server.username = Some("alice".to_string());
+        server.password = Some("hunter2".into());
+        store
+            .set_global(Some(server), Some(&mut vault))
+            .expect("save failed");
+        let mut reloaded = ProxyStore::load(&dir.0).expect("reload failed");
+        let mut edited = reloaded.global().cloned().expect("missing proxy");
+        edited.username = Some("bob".to_string());
+
+        // Act
+        reloaded
+            .set_global(Some(edited), Some(&mut vault))
+            .expect("save failed");
+
+        // Assert
+        assert_eq!(
+            vault.proxy_password("127.0.0.1", 1080),
+            Some(&"hunter2".into())
+        );
+    }
+
+    /// Removing a proxy forgets its password once no other entry still uses that proxy.
+    #[test]
+    fn store_forgets_password_of_removed_proxy() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut store = ProxyStore::load(&dir.0).expect("load failed");
+        let mut vault = CredentialVault::open(&dir.0, &VaultKey::Passphrase("secret".into()))
+            .expect("open failed");
+        let mut shared = proxy(ProxyKind::Socks5, 1080);
+        shared.username = Some("alice".to_string());
+        shared.password = Some("hunter2".into());
+        store
+            .set_global(Some(shared.clone()), Some(&mut vault))
+            .expect("save failed");
+        store
+            .set_server_proxy("voice.example", 64738, Some(shared), Some(&mut vault))
+            .expect("save failed");
+
+        // Act
+        store
+            .set_global(None, Some(&mut vault))
+            .expect("save failed");
+        let still_used = vault.proxy_password("127.0.0.1", 1080).cloned();
+        store
+            .set_server_proxy("voice.example", 64738, None, Some(&mut vault))
+            .expect("save failed");
+
+        // Assert
+        assert_eq!(still_used, Some("hunter2".into()));
+        assert_eq!(vault.proxy_password("127.0.0.1", 1080), None);
+    }
+}
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use zeroize::Zeroize;
+
+const REDACTED: &str = "[redacted]";
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl PartialEq<str> for SecretString {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for SecretString {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self)
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    pub fn new(value: Vec<u8>) -> Self {
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn expose_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(value: Vec<u8>) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{REDACTED} ({} bytes)", self.0.len())
+    }
+}
+
+impl fmt::Display for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SecretBytes, SecretString};
+
+    /// Debug and Display never print the wrapped value.
+    #[test]
+    fn formatting_is_redacted() {
+        // Arrange
+        let password = SecretString::from("hunter2");
+        let key = SecretBytes::from(vec![0xAB; 4]);
+
+        // Act
+        let formatted = format!(
+            "{password} {password:?} {:?} {key:?} {key}",
+            Some(&password)
+        );
+
+        // Assert
+        assert!(!formatted.contains("hunter2"));
+        assert_eq!(
+            formatted,
+            "[redacted] [redacted] Some([redacted]) [redacted] (4 bytes) [redacted]"
+        );
+    }
+
+    /// The wrapped value is still available explicitly and compares by content.
+    #[test]
+    fn expose_returns_value() {
+        // Arrange
+        let password = SecretString::from("hunter2".to_string());
+        let key = SecretBytes::new(vec![1, 2, 3]);
+
+        // Act
+        let exposed = password.expose();
+
+        // Assert
+        assert_eq!(exposed, "hunter2");
+        assert_eq!(password, "hunter2");
+        assert_eq!(key.expose(), [1, 2, 3]);
+        assert_eq!(key.len(), 3);
+    }
+
+    /// Serialized secrets are plain strings so stored files keep their format.
+    #[test]
+    fn secret_string_serializes_transparently() {
+        // Arrange
+        let tokens = vec![SecretString::from("red"), SecretString::from("blue")];
+
+        // Act
+        let json = serde_json::to_string(&tokens).expect("serialize failed");
+        let parsed: Vec<SecretString> = serde_json::from_str(&json).expect("parse failed");
+
+        // Assert
+        assert_eq!(json, r#"["red","blue"]"#);
+        assert_eq!(parsed, tokens);
+    }
+}
@@ -8,14 +8,14 @@ pub struct StateCache {
     users: HashMap<u32, User>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ChannelStateUpdate {
     pub id: u32,
     pub name: Option<String>,
     pub parent_id: Option<u32>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct UserStateUpdate {
     pub id: u32,
     pub name: Option<String>,
@@ -23,6 +23,11 @@ pub struct UserStateUpdate {
     pub muted: Option<bool>,
     pub deafened: Option<bool>,
     pub talking: Option<bool>,
+    pub mute: Option<bool>,
+    pub deaf: Option<bool>,
+    pub suppress: Option<bool>,
+    pub priority_speaker: Option<bool>,
+    pub recording: Option<bool>,
 }
 
 impl StateCache {
@@ -62,6 +67,11 @@ impl StateCache {
             muted: false,
             deafened: false,
             talking: false,
+            mute: false,
+            deaf: false,
+            suppress: false,
+            priority_speaker: false,
+            recording: false,
         });
 
         if let Some(name) = update.name {
@@ -83,12 +93,65 @@ impl StateCache {
         if let Some(talking) = update.talking {
             entry.talking = talking;
         }
+
+        if let Some(mute) = update.mute {
+            entry.mute = mute;
+        }
+
+        if let Some(deaf) = update.deaf {
+            entry.deaf = deaf;
+        }
+
+        if let Some(suppress) = update.suppress {
+            entry.suppress = suppress;
+        }
+
+        if let Some(priority_speaker) = update.priority_speaker {
+            entry.priority_speaker = priority_speaker;
+        }
+
+        if let Some(recording) = update.recording {
+            entry.recording = recording;
+        }
     }
 
     pub fn apply_user_remove(&mut self, id: u32) {
         self.users.remove(&id);
     }
 
+    pub fn apply_channel_remove(&mut self, id: u32) -> Vec<u32> {
+        let Some(channel) = self.channels.get(&id) else {
+            return Vec::new();
+        };
+        let fallback = channel.parent_id.unwrap_or(0);
+
+        let mut removed = vec![id];
+        let mut index = 0;
+        while index < removed.len() {
+            let parent = removed[index];
+            removed.extend(
+                self.channels
+                    .values()
+                    .filter(|channel| channel.parent_id == Some(parent))
+                    .map(|channel| channel.id),
+            );
+            index += 1;
+        }
+
+        for channel_id in &removed {
+            self.channels.remove(channel_id);
+        }
+        // Users left behind in a removed subtree follow the server and land in the parent channel.
+        for user in self.users.values_mut() {
+            if removed.contains(&user.channel_id) {
+                user.channel_id = fallback;
+            }
+        }
+
+        removed.sort_unstable();
+        removed
+    }
+
     pub fn channels(&self) -> Vec<Channel> {
         let mut channels = self.channels.values().cloned().collect::<Vec<_>>();
         channels.sort_by_key(|channel| channel.id);
@@ -151,6 +214,7 @@ mod tests {
             muted: Some(false),
             deafened: Some(false),
             talking: Some(false),
+            ..Default::default()
         });
 
         // Assert
@@ -168,6 +232,7 @@ mod tests {
             muted: Some(true),
             deafened: None,
             talking: Some(true),
+            ..Default::default()
         });
 
         // Assert
@@ -193,6 +258,7 @@ mod tests {
             muted: Some(false),
             deafened: Some(true),
             talking: Some(false),
+            ..Default::default()
         });
 
         cache.apply_user_state(UserStateUpdate {
@@ -202,6 +268,7 @@ mod tests {
             muted: None,
             deafened: None,
             talking: None,
+            ..Default::default()
         });
 
         // Assert
@@ -211,6 +278,39 @@ mod tests {
         assert!(!user.talking);
     }
 
+    /// Server-imposed flags merge independently from the self flags.
+    #[test]
+    fn user_state_tracks_server_flags() {
+        // Arrange
+        let mut cache = StateCache::new();
+        cache.apply_user_state(UserStateUpdate {
+            id: 13,
+            name: Some(String::from("Quinn")),
+            muted: Some(false),
+            mute: Some(true),
+            recording: Some(true),
+            ..Default::default()
+        });
+
+        // Act
+        cache.apply_user_state(UserStateUpdate {
+            id: 13,
+            suppress: Some(true),
+            priority_speaker: Some(true),
+            recording: Some(false),
+            ..Default::default()
+        });
+
+        // Assert
+        let user = cache.user(13).expect("user missing");
+        assert!(!user.muted);
+        assert!(user.mute);
+        assert!(!user.deaf);
+        assert!(user.suppress);
+        assert!(user.priority_speaker);
+        assert!(!user.recording);
+    }
+
     /// Removing a user deletes the cached entry.
     #[test]
     fn user_remove_deletes_user() {
@@ -225,6 +325,7 @@ mod tests {
             muted: None,
             deafened: None,
             talking: None,
+            ..Default::default()
         });
 
         // Assert
@@ -236,6 +337,77 @@ mod tests {
         assert!(cache.user(11).is_none());
     }
 
+    /// Removing a channel drops its subtree and moves orphaned users to the parent.
+    #[test]
+    fn channel_remove_cleans_up_subtree() {
+        // Arrange
+        let mut cache = StateCache::new();
+        for (id, parent_id) in [
+            (0, None),
+            (1, Some(0)),
+            (2, Some(1)),
+            (3, Some(2)),
+            (4, Some(0)),
+        ] {
+            cache.apply_channel_state(ChannelStateUpdate {
+                id,
+                name: Some(format!("Channel {id}")),
+                parent_id,
+            });
+        }
+        cache.apply_user_state(UserStateUpdate {
+            id: 10,
+            name: Some(String::from("Deep")),
+            channel_id: Some(3),
+            muted: None,
+            deafened: None,
+            talking: None,
+            ..Default::default()
+        });
+        cache.apply_user_state(UserStateUpdate {
+            id: 11,
+            name: Some(String::from("Elsewhere")),
+            channel_id: Some(4),
+            muted: None,
+            deafened: None,
+            talking: None,
+            ..Default::default()
+        });
+
+        // Act
+        let removed = cache.apply_channel_remove(1);
+
+        // Assert
+        assert_eq!(removed, vec![1, 2, 3]);
+        let ids = cache
+            .channels()
+            .into_iter()
+            .map(|channel| channel.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![0, 4]);
+        assert_eq!(cache.user(10).expect("user missing").channel_id, 0);
+        assert_eq!(cache.user(11).expect("user missing").channel_id, 4);
+    }
+
+    /// Removing an unknown channel leaves the cache untouched.
+    #[test]
+    fn channel_remove_ignores_unknown_channel() {
+        // Arrange
+        let mut cache = StateCache::new();
+        cache.apply_channel_state(ChannelStateUpdate {
+            id: 1,
+            name: Some(String::from("Lobby")),
+            parent_id: None,
+        });
+
+        // Act
+        let removed = cache.apply_channel_remove(9);
+
+        // Assert
+        assert!(removed.is_empty());
+        assert!(cache.channel(1).is_some());
+    }
+
     /// Channel and user snapshots are sorted by identifier.
     #[test]
     fn channels_and_users_return_sorted_snapshots() {
@@ -261,6 +433,7 @@ mod tests {
             muted: Some(false),
             deafened: Some(false),
             talking: Some(false),
+            ..Default::default()
         });
         cache.apply_user_state(UserStateUpdate {
             id: 10,
@@ -269,6 +442,7 @@ mod tests {
             muted: Some(false),
             deafened: Some(false),
             talking: Some(false),
+            ..Default::default()
         });
 
         // Assert
@@ -0,0 +1,385 @@
+use crate::mumble::certificate::ClientIdentity;
+use crate::mumble::connect::strip_brackets;
+use crate::mumble::control::HandshakeRequest;
+use crate::mumble::known_hosts::{certificate_fingerprint, verify_server_certificate};
+use crate::transport::errors::{ConnectPhase, TransportError};
+use openssl::ssl::{
+    SslConnector, SslConnectorBuilder, SslMethod, SslRef, SslStream, SslVerifyMode, SslVersion,
+};
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::{X509NameRef, X509Ref, X509VerifyResult};
+use serde::{Deserialize, Serialize};
+use std::net::TcpStream;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TlsVersion {
+    Tls10,
+    Tls11,
+    #[default]
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    fn ssl_version(self) -> SslVersion {
+        match self {
+            TlsVersion::Tls10 => SslVersion::TLS1,
+            TlsVersion::Tls11 => SslVersion::TLS1_1,
+            TlsVersion::Tls12 => SslVersion::TLS1_2,
+            TlsVersion::Tls13 => SslVersion::TLS1_3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsPolicy {
+    pub min_version: TlsVersion,
+    pub ca_bundle: Option<PathBuf>,
+    pub cipher_list: Option<String>,
+    pub ciphersuites: Option<String>,
+    pub verify_system_cas: bool,
+}
+
+impl Default for TlsPolicy {
+    fn default() -> Self {
+        Self {
+            min_version: TlsVersion::default(),
+            ca_bundle: None,
+            cipher_list: None,
+            ciphersuites: None,
+            verify_system_cas: true,
+        }
+    }
+}
+
+impl TlsPolicy {
+    pub fn connector(&self) -> Result<SslConnectorBuilder, TransportError> {
+        let mut builder = SslConnector::builder(SslMethod::tls())
+            .map_err(|err| TransportError::Io(format!("tls connector init failed: {err}")))?;
+        self.apply(&mut builder)?;
+        Ok(builder)
+    }
+
+    pub fn apply(&self, builder: &mut SslConnectorBuilder) -> Result<(), TransportError> {
+        builder
+            .set_min_proto_version(Some(self.min_version.ssl_version()))
+            .map_err(|err| invalid(format!("unsupported minimum TLS version: {err}")))?;
+        if let Some(cipher_list) = &self.cipher_list {
+            builder
+                .set_cipher_list(cipher_list)
+                .map_err(|err| invalid(format!("invalid TLS cipher list: {err}")))?;
+        }
+        if let Some(ciphersuites) = &self.ciphersuites {
+            builder
+                .set_ciphersuites(ciphersuites)
+                .map_err(|err| invalid(format!("invalid TLS 1.3 ciphersuites: {err}")))?;
+        }
+
+        // Chain verification only decides trust for unpinned servers, so with system CAs off
+        // a server is trusted through its pin or the extra bundle alone.
+        if !self.verify_system_cas {
+            let store = X509StoreBuilder::new()
+                .map_err(|err| TransportError::Io(format!("certificate store init failed: {err}")))?
+                .build();
+            builder.set_cert_store(store);
+        }
+        if let Some(ca_bundle) = &self.ca_bundle {
+            builder.set_ca_file(ca_bundle).map_err(|err| {
+                invalid(format!(
+                    "invalid CA bundle {}: {err}",
+                    ca_bundle.to_string_lossy()
+                ))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub fingerprint: String,
+}
+
+impl CertificateInfo {
+    pub fn from_certificate(certificate: &X509Ref) -> Result<Self, TransportError> {
+        Ok(Self {
+            subject: name_to_string(certificate.subject_name()),
+            issuer: name_to_string(certificate.issuer_name()),
+            not_before: certificate.not_before().to_string(),
+            not_after: certificate.not_after().to_string(),
+            fingerprint: certificate_fingerprint(certificate)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsSessionInfo {
+    pub version: String,
+    pub cipher: String,
+    pub chain: Vec<CertificateInfo>,
+    pub fingerprint: String,
+}
+
+impl TlsSessionInfo {
+    pub fn from_ssl(ssl: &SslRef) -> Result<Self, TransportError> {
+        let certificate = ssl
+            .peer_certificate()
+            .ok_or_else(|| TransportError::Protocol("server sent no certificate".to_string()))?;
+        Ok(Self {
+            version: ssl.version_str().to_string(),
+            cipher: ssl
+                .current_cipher()
+                .map(|cipher| cipher.name().to_string())
+                .unwrap_or_default(),
+            chain: peer_chain(ssl)?,
+            fingerprint: certificate_fingerprint(&certificate)?,
+        })
+    }
+}
+
+pub fn peer_chain(ssl: &SslRef) -> Result<Vec<CertificateInfo>, TransportError> {
+    // On the client side the peer chain starts with the server's own certificate.
+    match ssl.peer_cert_chain() {
+        Some(chain) => chain
+            .iter()
+            .map(CertificateInfo::from_certificate)
+            .collect(),
+        None => ssl
+            .peer_certificate()
+            .iter()
+            .map(|certificate| CertificateInfo::from_certificate(certificate))
+            .collect(),
+    }
+}
+
+pub fn tls_handshake(
+    request: &HandshakeRequest,
+    tcp: TcpStream,
+) -> Result<SslStream<TcpStream>, TransportError> {
+    let mut builder = request.tls.connector()?;
+    if let Some(certificate) = &request.certificate {
+        ClientIdentity::load(certificate)?.apply(&mut builder)?;
+    }
+    // Murmur servers are usually self-signed, so chain errors are judged after the handshake.
+    builder.set_verify_callback(SslVerifyMode::PEER, |_, _| true);
+    builder
+        .build()
+        .connect(strip_brackets(&request.server), tcp)
+        .map_err(|err| TransportError::Connect {
+            phase: ConnectPhase::Tls,
+            message: err.to_string(),
+        })
+}
+
+pub fn verify_peer(request: &HandshakeRequest, ssl: &SslRef) -> Result<String, TransportError> {
+    let certificate = ssl
+        .peer_certificate()
+        .ok_or_else(|| TransportError::Protocol("server sent no certificate".to_string()))?;
+    let fingerprint = certificate_fingerprint(&certificate)?;
+    verify_server_certificate(
+        &request.server,
+        request.port,
+        request.trusted_fingerprint.as_deref(),
+        &fingerprint,
+        ssl.verify_result() == X509VerifyResult::OK,
+    )?;
+    Ok(fingerprint)
+}
+
+fn name_to_string(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry
+                .data()
+                .as_utf8()
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            format!("{key}={value}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn invalid(message: String) -> TransportError {
+    TransportError::InvalidConfig(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TlsPolicy, TlsSessionInfo, TlsVersion};
+    use crate::mumble::certificate::tests::self_signed;
+    use crate::mumble::identity::tests::TempDir;
+    use crate::mumble::known_hosts::certificate_fingerprint;
+    use crate::transport::errors::TransportError;
+    use openssl::ssl::{SslAcceptor, SslMethod, SslVerifyMode, SslVersion};
+    use openssl::x509::{X509VerifyResult, X509};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread::JoinHandle;
+
+    fn tls_server(max_version: SslVersion) -> (u16, X509, JoinHandle<()>) {
+        let (certificate, key) = self_signed("localhost");
+        let mut acceptor =
+            SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).expect("acceptor failed");
+        acceptor.set_certificate(&certificate).expect("cert failed");
+        acceptor.set_private_key(&key).expect("key failed");
+        acceptor
+            .set_max_proto_version(Some(max_version))
+            .expect("max version failed");
+        let acceptor = acceptor.build();
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind failed");
+        let port = listener.local_addr().expect("missing address").port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("accept failed");
+            let _ = acceptor.accept(stream);
+        });
+        (port, certificate, server)
+    }
+
+    fn connect(
+        policy: &TlsPolicy,
+        port: u16,
+    ) -> Result<openssl::ssl::SslStream<TcpStream>, String> {
+        let mut builder = policy.connector().expect("connector failed");
+        builder.set_verify_callback(SslVerifyMode::PEER, |_, _| true);
+        let tcp = TcpStream::connect(("127.0.0.1", port)).expect("connect failed");
+        builder
+            .build()
+            .connect("localhost", tcp)
+            .map_err(|err| err.to_string())
+    }
+
+    /// The default policy requires TLS 1.2 and trusts the system CAs.
+    #[test]
+    fn default_policy_requires_tls12() {
+        // Arrange
+        // Act
+        let policy = TlsPolicy::default();
+
+        // Assert
+        assert_eq!(policy.min_version, TlsVersion::Tls12);
+        assert!(policy.verify_system_cas);
+        assert!(policy.ca_bundle.is_none());
+    }
+
+    /// Servers below the configured minimum version are refused.
+    #[test]
+    fn min_version_rejects_older_servers() {
+        // Arrange
+        let (port, _, server) = tls_server(SslVersion::TLS1_2);
+        let policy = TlsPolicy {
+            min_version: TlsVersion::Tls13,
+            ..TlsPolicy::default()
+        };
+
+        // Act
+        let result = connect(&policy, port);
+        server.join().expect("server panicked");
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    /// Cipher preferences constrain the negotiated cipher.
+    #[test]
+    fn cipher_list_selects_cipher() {
+        // Arrange
+        let (port, _, server) = tls_server(SslVersion::TLS1_2);
+        let policy = TlsPolicy {
+            cipher_list: Some("ECDHE-RSA-AES128-GCM-SHA256".to_string()),
+            ..TlsPolicy::default()
+        };
+
+        // Act
+        let stream = connect(&policy, port).expect("handshake failed");
+        server.join().expect("server panicked");
+
+        // Assert
+        assert_eq!(stream.ssl().version2(), Some(SslVersion::TLS1_2));
+        assert_eq!(
+            stream.ssl().current_cipher().map(|cipher| cipher.name()),
+            Some("ECDHE-RSA-AES128-GCM-SHA256")
+        );
+    }
+
+    /// A private CA bundle makes the server chain verify even with system CAs disabled.
+    #[test]
+    fn ca_bundle_verifies_private_chain() {
+        // Arrange
+        let (port, certificate, server) = tls_server(SslVersion::TLS1_3);
+        let dir = TempDir::new();
+        let bundle = dir.0.join("ca.pem");
+        std::fs::write(&bundle, certificate.to_pem().expect("pem failed")).expect("write failed");
+        let private_only = TlsPolicy {
+            ca_bundle: Some(bundle),
+            verify_system_cas: false,
+            ..TlsPolicy::default()
+        };
+
+        // Act
+        let stream = connect(&private_only, port).expect("handshake failed");
+        server.join().expect("server panicked");
+        let (port, _, server) = tls_server(SslVersion::TLS1_3);
+        let unknown = connect(&TlsPolicy::default(), port).expect("handshake failed");
+        server.join().expect("server panicked");
+
+        // Assert
+        assert_eq!(stream.ssl().verify_result(), X509VerifyResult::OK);
+        assert_ne!(unknown.ssl().verify_result(), X509VerifyResult::OK);
+    }
+
+    /// Invalid cipher strings and missing CA bundles are configuration errors.
+    #[test]
+    fn connector_rejects_invalid_policy() {
+        // Arrange
+        let bad_ciphers = TlsPolicy {
+            cipher_list: Some("NOT-A-CIPHER".to_string()),
+            ..TlsPolicy::default()
+        };
+        let missing_bundle = TlsPolicy {
+            ca_bundle: Some("/nonexistent/ca.pem".into()),
+            ..TlsPolicy::default()
+        };
+
+        // Act
+        let bad_ciphers = bad_ciphers.connector();
+        let missing_bundle = missing_bundle.connector();
+
+        // Assert
+        assert!(matches!(bad_ciphers, Err(TransportError::InvalidConfig(_))));
+        assert!(matches!(
+            missing_bundle,
+            Err(TransportError::InvalidConfig(_))
+        ));
+    }
+
+    /// Session details report the negotiated version, cipher and server certificate.
+    #[test]
+    fn session_info_captures_negotiated_parameters() {
+        // Arrange
+        let (port, certificate, server) = tls_server(SslVersion::TLS1_3);
+        let stream = connect(&TlsPolicy::default(), port).expect("handshake failed");
+        server.join().expect("server panicked");
+
+        // Act
+        let info = TlsSessionInfo::from_ssl(stream.ssl()).expect("session info failed");
+
+        // Assert
+        assert_eq!(info.version, "TLSv1.3");
+        assert!(info.cipher.starts_with("TLS_"));
+        assert_eq!(
+            info.fingerprint,
+            certificate_fingerprint(&certificate).expect("fingerprint failed")
+        );
+        assert_eq!(info.chain.len(), 1);
+        assert_eq!(info.chain[0].subject, "CN=localhost");
+        assert_eq!(info.chain[0].issuer, "CN=localhost");
+        assert_eq!(info.chain[0].fingerprint, info.fingerprint);
+    }
+}
@@ -1,12 +1,25 @@
 use crate::mumble::state::StateCache;
-#[cfg(not(feature = "coverage"))]
-use crate::mumble::{tls_connect, SocketControlConnector};
 use crate::mumble::{
-    ControlConnector, ControlMessage, ControlSession, HandshakeRequest, MumbleConfig,
-    NoopControlConnector, TransportEvent, UserStateCommand,
+    run_diagnostics, ControlConnector, ControlMessage, ControlSession, CredentialVault, CryptStats,
+    DiagnosticReport, MumbleConfig, NoopControlConnector, RemovalKind, RemovalNotice, SecretBytes,
+    SecretString, TextMessage, TextMessageCommand, TlsSessionInfo, TransportEvent,
+    UserStateCommand, VoiceChannel, VoiceCrypt, VoicePath,
 };
+#[cfg(not(feature = "coverage"))]
+use crate::mumble::{tls_connect, SocketControlConnector};
 use crate::transport::errors::TransportError;
-use crate::transport::types::ConnState;
+use crate::transport::types::{ConnState, ServerInfo};
+use mumble_protocol_2x::voice::{Clientbound, Serverbound, VoicePacket};
+use std::net::SocketAddr;
+use std::time::Instant;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TextTarget {
+    CurrentChannel,
+    Channel(u32),
+    ChannelTree(u32),
+    Users(Vec<u32>),
+}
 
 pub struct MumbleTransport {
     config: MumbleConfig,
@@ -16,7 +29,15 @@ pub struct MumbleTransport {
     state: StateCache,
     session_id: Option<u32>,
     current_channel_id: Option<u32>,
+    server_info: Option<ServerInfo>,
+    tls_session: Option<TlsSessionInfo>,
+    control_peer: Option<SocketAddr>,
     control_session: Option<Box<dyn ControlSession>>,
+    voice: Option<VoiceChannel>,
+    voice_path: Option<VoicePath>,
+    incoming_voice: Vec<VoicePacket<Clientbound>>,
+    auto_unmute: bool,
+    vault: Option<CredentialVault>,
 }
 
 impl MumbleTransport {
@@ -39,7 +60,15 @@ impl MumbleTransport {
             state: StateCache::new(),
             session_id: None,
             current_channel_id: None,
+            server_info: None,
+            tls_session: None,
+            control_peer: None,
             control_session: None,
+            voice: None,
+            voice_path: None,
+            incoming_voice: Vec::new(),
+            auto_unmute: false,
+            vault: None,
         }
     }
 
@@ -59,40 +88,84 @@ impl MumbleTransport {
         self.current_channel_id
     }
 
-    pub fn connect(&mut self) -> Result<(), TransportError> {
-        if self.conn_state != ConnState::Disconnected {
-            return Ok(());
+    pub fn server_info(&self) -> Option<&ServerInfo> {
+        self.server_info.as_ref()
+    }
+
+    pub fn tls_session(&self) -> Option<&TlsSessionInfo> {
+        self.tls_session.as_ref()
+    }
+
+    pub fn voice_stats(&self) -> Option<CryptStats> {
+        self.voice.as_ref().map(VoiceChannel::stats)
+    }
+
+    pub fn take_voice_packets(&mut self) -> Vec<VoicePacket<Clientbound>> {
+        std::mem::take(&mut self.incoming_voice)
+    }
+
+    pub fn voice_path(&self) -> Option<VoicePath> {
+        self.voice_path
+    }
+
+    pub fn set_force_tcp_voice(&mut self, forced: bool) {
+        self.config.force_tcp_voice = forced;
+        if self.voice_path.is_some() {
+            self.update_voice_path(Instant::now());
         }
+    }
 
-        let server = self.config.server.trim();
-        if server.is_empty() {
-            return Err(TransportError::InvalidConfig(
-                "server is required".to_string(),
-            ));
+    pub fn send_voice(&mut self, packet: VoicePacket<Serverbound>) -> Result<(), TransportError> {
+        if self.conn_state != ConnState::Connected {
+            return Err(TransportError::Disconnected);
         }
-        let username = self.config.username.trim();
-        if username.is_empty() {
-            return Err(TransportError::InvalidConfig(
-                "username is required".to_string(),
-            ));
+        match self.voice_path {
+            Some(VoicePath::Udp) => self
+                .voice
+                .as_mut()
+                .ok_or_else(|| TransportError::Protocol("voice channel unavailable".to_string()))?
+                .send(packet),
+            Some(VoicePath::Tunnel) => self
+                .control_session
+                .as_mut()
+                .ok_or_else(|| TransportError::Protocol("control session unavailable".to_string()))?
+                .send_voice(packet),
+            None => Err(TransportError::Protocol(
+                "voice channel unavailable".to_string(),
+            )),
+        }
+    }
+
+    pub fn diagnose(&self) -> DiagnosticReport {
+        run_diagnostics(&self.config, self.conn_state != ConnState::Disconnected)
+    }
+
+    pub fn connect(&mut self) -> Result<(), TransportError> {
+        if self.conn_state != ConnState::Disconnected {
+            return Ok(());
         }
 
+        let request = self.config.handshake_request()?;
+        self.reset_session();
         self.set_conn_state(ConnState::Connecting);
-        let request = HandshakeRequest {
-            server: self.config.server.clone(),
-            port: self.config.port,
-            username: self.config.username.clone(),
-            password: self.config.password.clone(),
-        };
         let handshake = match self.control.handshake(request) {
             Ok(handshake) => handshake,
             Err(error) => {
                 self.set_conn_state(ConnState::Error);
+                if let TransportError::UntrustedCertificate(certificate) = &error {
+                    self.events
+                        .push(TransportEvent::UntrustedCertificate(certificate.clone()));
+                }
                 self.events.push(TransportEvent::Error(error.to_string()));
                 return Err(error);
             }
         };
         self.control_session = handshake.session;
+        self.control_peer = handshake.peer;
+        if let Some(tls) = handshake.tls {
+            self.tls_session = Some(tls.clone());
+            self.events.push(TransportEvent::TlsSession(tls));
+        }
 
         for message in handshake.messages {
             self.apply_control_message(message);
@@ -102,6 +175,54 @@ impl MumbleTransport {
         Ok(())
     }
 
+    pub fn disconnect(&mut self) -> Result<(), TransportError> {
+        if self.conn_state == ConnState::Disconnected {
+            return Ok(());
+        }
+
+        let closed = match self.control_session.take() {
+            Some(mut session) => session.close(),
+            None => Ok(()),
+        };
+        self.tear_down();
+        closed
+    }
+
+    pub fn poll(&mut self) -> Result<(), TransportError> {
+        if self.conn_state != ConnState::Connected {
+            return Ok(());
+        }
+
+        loop {
+            let next = match self.control_session.as_mut() {
+                Some(session) => session.try_recv(),
+                None => return Ok(()),
+            };
+            match next {
+                Ok(Some(message)) => {
+                    self.apply_control_message(message);
+                    if self.conn_state != ConnState::Connected {
+                        return Ok(());
+                    }
+                }
+                Ok(None) => break,
+                Err(error) => {
+                    self.control_session = None;
+                    self.voice = None;
+                    if matches!(error, TransportError::Disconnected) {
+                        self.set_conn_state(ConnState::Disconnected);
+                    } else {
+                        self.set_conn_state(ConnState::Error);
+                        self.events.push(TransportEvent::Error(error.to_string()));
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        self.poll_voice();
+        Ok(())
+    }
+
     pub fn join_channel(&mut self, channel_id: u32) -> Result<(), TransportError> {
         if self.conn_state != ConnState::Connected {
             return Err(TransportError::Disconnected);
@@ -127,7 +248,7 @@ impl MumbleTransport {
             .ok_or_else(|| TransportError::Protocol("control session unavailable".to_string()))?;
         session.send_user_state(UserStateCommand {
             session_id,
-            channel_id,
+            channel_id: Some(channel_id),
             muted: None,
             deafened: None,
         })?;
@@ -140,6 +261,7 @@ impl MumbleTransport {
                 muted: None,
                 deafened: None,
                 talking: None,
+                ..Default::default()
             });
         self.current_channel_id = Some(channel_id);
         let users = self.state.users();
@@ -147,6 +269,166 @@ impl MumbleTransport {
         Ok(())
     }
 
+    pub fn set_self_mute(&mut self, muted: bool) -> Result<(), TransportError> {
+        let (_, deafened) = self.self_flags()?;
+        if muted {
+            self.auto_unmute = false;
+            return self.send_self_flags(true, deafened);
+        }
+
+        // Unmuting while deafened also undeafens, matching the official client.
+        self.auto_unmute = false;
+        self.send_self_flags(false, false)
+    }
+
+    pub fn set_self_deafen(&mut self, deafened: bool) -> Result<(), TransportError> {
+        let (muted, _) = self.self_flags()?;
+        if deafened {
+            if !muted {
+                self.auto_unmute = true;
+            }
+            return self.send_self_flags(true, true);
+        }
+
+        let muted = muted && !self.auto_unmute;
+        self.auto_unmute = false;
+        self.send_self_flags(muted, false)
+    }
+
+    pub fn set_credential_vault(&mut self, vault: CredentialVault) {
+        self.vault = Some(vault);
+    }
+
+    pub fn set_access_tokens(&mut self, tokens: Vec<SecretString>) -> Result<(), TransportError> {
+        if self.conn_state != ConnState::Connected {
+            return Err(TransportError::Disconnected);
+        }
+
+        let session = self
+            .control_session
+            .as_mut()
+            .ok_or_else(|| TransportError::Protocol("control session unavailable".to_string()))?;
+        // Save before sending, so a failed save leaves the server and config untouched.
+        if let Some(vault) = self.vault.as_mut() {
+            vault.set_tokens(&self.config.server, self.config.port, tokens.clone())?;
+        }
+        session.send_tokens(tokens.clone())?;
+        self.config.tokens = tokens;
+        Ok(())
+    }
+
+    pub fn send_text(&mut self, target: TextTarget, message: String) -> Result<(), TransportError> {
+        if self.conn_state != ConnState::Connected {
+            return Err(TransportError::Disconnected);
+        }
+
+        if message.trim().is_empty() {
+            return Err(TransportError::Protocol("empty text message".to_string()));
+        }
+
+        let mut command = TextMessageCommand {
+            channel_ids: Vec::new(),
+            tree_ids: Vec::new(),
+            session_ids: Vec::new(),
+            message,
+        };
+        match target {
+            TextTarget::CurrentChannel => {
+                let channel_id = self.current_channel_id.ok_or_else(|| {
+                    TransportError::Protocol("missing current channel".to_string())
+                })?;
+                command.channel_ids.push(channel_id);
+            }
+            TextTarget::Channel(channel_id) => command.channel_ids.push(channel_id),
+            TextTarget::ChannelTree(channel_id) => command.tree_ids.push(channel_id),
+            TextTarget::Users(session_ids) => {
+                if session_ids.is_empty() {
+                    return Err(TransportError::Protocol("no text recipients".to_string()));
+                }
+                command.session_ids = session_ids;
+            }
+        }
+
+        let session = self
+            .control_session
+            .as_mut()
+            .ok_or_else(|| TransportError::Protocol("control session unavailable".to_string()))?;
+        session.send_text(command.clone())?;
+
+        // Servers do not echo our own messages back, so surface them locally for the chat log.
+        self.events.push(TransportEvent::Text(TextMessage {
+            actor_id: self.session_id,
+            channel_ids: command.channel_ids,
+            tree_ids: command.tree_ids,
+            user_ids: command.session_ids,
+            message: command.message,
+        }));
+        Ok(())
+    }
+
+    fn tear_down(&mut self) {
+        self.reset_session();
+        self.events.push(TransportEvent::Channels(Vec::new()));
+        self.events.push(TransportEvent::Users(Vec::new()));
+        self.set_conn_state(ConnState::Disconnected);
+    }
+
+    fn reset_session(&mut self) {
+        self.state = StateCache::new();
+        self.session_id = None;
+        self.current_channel_id = None;
+        self.server_info = None;
+        self.tls_session = None;
+        self.control_peer = None;
+        self.voice = None;
+        self.voice_path = None;
+        self.incoming_voice.clear();
+        self.auto_unmute = false;
+    }
+
+    fn self_flags(&self) -> Result<(bool, bool), TransportError> {
+        if self.conn_state != ConnState::Connected {
+            return Err(TransportError::Disconnected);
+        }
+
+        let session_id = self
+            .session_id
+            .ok_or_else(|| TransportError::Protocol("missing session id".to_string()))?;
+        let user = self
+            .state
+            .user(session_id)
+            .ok_or_else(|| TransportError::Protocol("missing self user state".to_string()))?;
+        Ok((user.muted, user.deafened))
+    }
+
+    fn send_self_flags(&mut self, muted: bool, deafened: bool) -> Result<(), TransportError> {
+        let session_id = self
+            .session_id
+            .ok_or_else(|| TransportError::Protocol("missing session id".to_string()))?;
+        let session = self
+            .control_session
+            .as_mut()
+            .ok_or_else(|| TransportError::Protocol("control session unavailable".to_string()))?;
+        session.send_user_state(UserStateCommand {
+            session_id,
+            channel_id: None,
+            muted: Some(muted),
+            deafened: Some(deafened),
+        })?;
+
+        // Apply optimistically; the server's echoed UserState is authoritative and overwrites this.
+        self.state
+            .apply_user_state(crate::mumble::state::UserStateUpdate {
+                id: session_id,
+                muted: Some(muted),
+                deafened: Some(deafened),
+                ..Default::default()
+            });
+        let users = self.state.users();
+        self.events.push(TransportEvent::Users(users));
+        Ok(())
+    }
+
     fn set_conn_state(&mut self, next: ConnState) {
         self.conn_state = next;
         self.events.push(TransportEvent::ConnectionState(next));
@@ -154,62 +436,220 @@ impl MumbleTransport {
 
     fn apply_control_message(&mut self, message: ControlMessage) {
         match message {
+            ControlMessage::ServerVersion(info) => {
+                self.server_info = Some(info.clone());
+                self.events.push(TransportEvent::ServerInfo(info));
+            }
             ControlMessage::ServerSync { session } => {
                 self.session_id = Some(session);
+                // Our own UserState arrives before ServerSync, while the session is still unknown.
+                if let Some(user) = self.state.user(session) {
+                    self.current_channel_id = Some(user.channel_id);
+                }
             }
-            ControlMessage::ChannelState {
-                id,
-                name,
-                parent_id,
-            } => {
-                self.state
-                    .apply_channel_state(crate::mumble::state::ChannelStateUpdate {
-                        id,
-                        name: Some(name),
-                        parent_id,
-                    });
+            ControlMessage::Pong { rtt } => {
+                self.events.push(TransportEvent::Latency(rtt));
+            }
+            ControlMessage::ChannelState(update) => {
+                self.state.apply_channel_state(update);
                 let channels = self.state.channels();
                 self.events.push(TransportEvent::Channels(channels));
             }
-            ControlMessage::UserState {
+            ControlMessage::UserState(update) => {
+                if self.session_id == Some(update.id) && update.channel_id.is_some() {
+                    self.current_channel_id = update.channel_id;
+                }
+                self.state.apply_user_state(update);
+                let users = self.state.users();
+                self.events.push(TransportEvent::Users(users));
+            }
+            ControlMessage::UserRemove {
                 id,
-                name,
-                channel_id,
-                muted,
-                deafened,
-                talking,
+                actor_id,
+                reason,
+                ban,
             } => {
                 if self.session_id == Some(id) {
-                    self.current_channel_id = Some(channel_id);
+                    let kind = if ban {
+                        RemovalKind::Ban
+                    } else {
+                        RemovalKind::Kick
+                    };
+                    self.events.push(TransportEvent::Removed(RemovalNotice {
+                        kind,
+                        actor_id,
+                        reason,
+                    }));
+                    self.control_session = None;
+                    self.tear_down();
+                    return;
+                }
+                self.state.apply_user_remove(id);
+                let users = self.state.users();
+                self.events.push(TransportEvent::Users(users));
+            }
+            ControlMessage::TextMessage(message) => {
+                self.events.push(TransportEvent::Text(message));
+            }
+            ControlMessage::ChannelRemove { id } => {
+                if self.state.apply_channel_remove(id).is_empty() {
+                    return;
                 }
-                self.state
-                    .apply_user_state(crate::mumble::state::UserStateUpdate {
-                        id,
-                        name: Some(name),
-                        channel_id: Some(channel_id),
-                        muted: Some(muted),
-                        deafened: Some(deafened),
-                        talking: Some(talking),
-                    });
+                if let Some(user) = self.session_id.and_then(|session| self.state.user(session)) {
+                    self.current_channel_id = Some(user.channel_id);
+                }
+                let channels = self.state.channels();
+                self.events.push(TransportEvent::Channels(channels));
                 let users = self.state.users();
                 self.events.push(TransportEvent::Users(users));
             }
+            ControlMessage::CryptSetup {
+                key,
+                client_nonce,
+                server_nonce,
+            } => {
+                if let Err(error) = self.apply_crypt_setup(key, client_nonce, server_nonce) {
+                    self.events
+                        .push(TransportEvent::Error(format!("voice unavailable: {error}")));
+                }
+            }
+            ControlMessage::Voice(packet) => {
+                // Servers only tunnel audio; UDP pings are measured on the voice channel itself.
+                if matches!(packet, VoicePacket::Audio { .. }) {
+                    self.incoming_voice.push(packet);
+                }
+            }
+        }
+    }
+
+    fn apply_crypt_setup(
+        &mut self,
+        key: Option<SecretBytes>,
+        client_nonce: Option<Vec<u8>>,
+        server_nonce: Option<Vec<u8>>,
+    ) -> Result<(), TransportError> {
+        match (key, client_nonce, server_nonce) {
+            (Some(key), Some(client_nonce), Some(server_nonce)) => {
+                let now = Instant::now();
+                let opened = self.open_voice(&key, &client_nonce, &server_nonce, now);
+                // Voice starts tunnelled and moves to UDP once pings come back.
+                self.update_voice_path(now);
+                opened
+            }
+            (_, _, Some(server_nonce)) => match self.voice.as_mut() {
+                Some(voice) => voice.crypt_mut().set_server_nonce(&server_nonce),
+                None => Ok(()),
+            },
+            // A CryptSetup without a server nonce asks us to resend ours.
+            _ => match (self.voice.as_ref(), self.control_session.as_mut()) {
+                (Some(voice), Some(session)) => {
+                    session.send_crypt_setup(Some(voice.crypt().client_nonce()))
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+
+    fn open_voice(
+        &mut self,
+        key: &SecretBytes,
+        client_nonce: &[u8],
+        server_nonce: &[u8],
+        now: Instant,
+    ) -> Result<(), TransportError> {
+        self.voice = None;
+        let crypt = VoiceCrypt::new(key, client_nonce, server_nonce, now)?;
+        // UDP cannot pass through the proxy, so voice stays on the tunnel there.
+        if self.config.proxy.is_some() {
+            return Ok(());
+        }
+        // Voice must reach the host the control connection reached, not a fresh lookup result.
+        let Some(server) = self.control_peer else {
+            return Ok(());
+        };
+        let interval = self.config.keepalive.interval;
+        self.voice = Some(VoiceChannel::open(server, crypt, interval, now)?);
+        Ok(())
+    }
+
+    fn update_voice_path(&mut self, now: Instant) {
+        let udp = !self.config.force_tcp_voice
+            && self
+                .voice
+                .as_ref()
+                .is_some_and(|voice| voice.udp_alive(now));
+        let path = if udp {
+            VoicePath::Udp
+        } else {
+            VoicePath::Tunnel
+        };
+        if self.voice_path != Some(path) {
+            self.voice_path = Some(path);
+            self.events.push(TransportEvent::VoicePath(path));
+        }
+    }
+
+    fn poll_voice(&mut self) {
+        if self.voice_path.is_none() {
+            return;
+        }
+        let now = Instant::now();
+        // Forced TCP keeps the socket quiet; pings resume when the setting is lifted.
+        if !self.config.force_tcp_voice {
+            self.poll_udp(now);
+        }
+        self.update_voice_path(now);
+    }
+
+    fn poll_udp(&mut self, now: Instant) {
+        let Some(voice) = self.voice.as_mut() else {
+            return;
+        };
+        match voice.poll(now) {
+            Ok(packets) => self.incoming_voice.extend(packets),
+            Err(error) => {
+                self.voice = None;
+                self.events
+                    .push(TransportEvent::Error(format!("voice unavailable: {error}")));
+                return;
+            }
+        }
+        if voice.take_resync_request() {
+            if let Some(session) = self.control_session.as_mut() {
+                if let Err(error) = session.send_crypt_setup(None) {
+                    self.events.push(TransportEvent::Error(error.to_string()));
+                }
+            }
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::MumbleTransport;
+    use super::{MumbleTransport, TextTarget};
     use crate::mumble::config::DEFAULT_PORT;
+    use crate::mumble::connect::ConnectTimeouts;
+    use crate::mumble::crypt::tests::{audio, KEY};
+    use crate::mumble::diagnostics::tests::stand_in_server;
+    use crate::mumble::identity::tests::TempDir;
+    use crate::mumble::keepalive::KeepaliveConfig;
+    use crate::mumble::state::{ChannelStateUpdate, UserStateUpdate};
+    use crate::mumble::tls::{CertificateInfo, TlsPolicy, TlsSessionInfo, TlsVersion};
     use crate::mumble::{
-        ControlConnector, ControlHandshake, ControlMessage, ControlSession, HandshakeRequest,
-        MumbleConfig, UserStateCommand,
+        ClientCertificate, ControlConnector, ControlHandshake, ControlMessage, ControlSession,
+        CredentialVault, Credentials, DiagnosticStage, HandshakeRequest, MumbleConfig, RemovalKind,
+        RemovalNotice, SecretBytes, SecretString, StageStatus, TextMessage, TextMessageCommand,
+        UserStateCommand, VaultKey, VoicePath,
     };
-    use crate::transport::errors::TransportError;
-    use crate::transport::types::ConnState;
+    use crate::transport::errors::{RejectKind, TransportError};
+    use crate::transport::types::{ConnState, ProtocolVersion, ServerInfo, UntrustedCertificate};
+    use bytes::BytesMut;
+    use mumble_protocol_2x::crypt::ServerCryptState;
+    use mumble_protocol_2x::voice::{Serverbound, VoicePacket, VoicePacketPayload};
     use std::cell::RefCell;
+    use std::net::UdpSocket;
     use std::rc::Rc;
+    use std::time::{Duration, Instant};
 
     #[derive(Default)]
     struct TestControlConnector {
@@ -229,6 +669,8 @@ mod tests {
             Ok(ControlHandshake {
                 messages: Vec::new(),
                 session: None,
+                tls: None,
+                peer: None,
             })
         }
     }
@@ -341,6 +783,13 @@ mod tests {
                 port: DEFAULT_PORT,
                 username: "tester".to_string(),
                 password: None,
+                certificate: None,
+                trusted_fingerprint: None,
+                tls: TlsPolicy::default(),
+                proxy: None,
+                tokens: Vec::new(),
+                keepalive: KeepaliveConfig::default(),
+                timeouts: ConnectTimeouts::default(),
             }
         );
     }
@@ -377,48 +826,52 @@ mod tests {
         ));
     }
 
-    /// Server sync control messages update the stored session id.
+    /// Server rejections are returned as typed errors so callers can react to them.
     #[test]
-    fn connect_applies_server_sync() {
+    fn connect_surfaces_rejection() {
         // Arrange
         let config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
-        let capture = Rc::new(RefCell::new(None));
-        let messages = vec![ControlMessage::ServerSync { session: 42 }];
-        let connector = TestControlConnectorWithMessages {
-            last_request: Rc::clone(&capture),
-            messages,
+        let connector = TestRejectingConnector {
+            kind: RejectKind::WrongServerPW,
         };
         let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
 
         // Act
-        transport.connect().expect("connect failed");
+        let err = transport.connect().expect_err("expected connect to fail");
 
         // Assert
-        assert_eq!(transport.session_id(), Some(42));
+        assert!(matches!(
+            err,
+            TransportError::Rejected {
+                kind: RejectKind::WrongServerPW,
+                ..
+            }
+        ));
+        assert_eq!(transport.conn_state(), ConnState::Error);
     }
 
-    /// Channel state messages update cached channels and emit events.
+    /// The configured client certificate is loaded and forwarded with the handshake.
     #[test]
-    fn connect_applies_channel_state() {
+    fn connect_forwards_client_certificate() {
         // Arrange
-        let config = MumbleConfig::new(
+        let (certificate, key) = crate::mumble::certificate::tests::self_signed("tester");
+        let mut bundle = certificate.to_pem().expect("cert pem failed");
+        bundle.extend(key.private_key_to_pem_pkcs8().expect("key pem failed"));
+        let bundle = String::from_utf8(bundle).expect("pem is not utf-8");
+        let mut config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
+        config.cert_pem = Some(bundle.clone().into());
         let capture = Rc::new(RefCell::new(None));
-        let messages = vec![ControlMessage::ChannelState {
-            id: 1,
-            name: "Lobby".to_string(),
-            parent_id: None,
-        }];
-        let connector = TestControlConnectorWithMessages {
+        let connector = TestControlConnector {
             last_request: Rc::clone(&capture),
-            messages,
+            fail: false,
         };
         let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
 
@@ -426,372 +879,2034 @@ mod tests {
         transport.connect().expect("connect failed");
 
         // Assert
-        let events = transport
-            .take_events()
-            .into_iter()
-            .filter_map(|event| match event {
-                super::TransportEvent::Channels(channels) => Some(channels),
-                _ => None,
+        let request = capture.borrow().clone().expect("missing request");
+        assert_eq!(
+            request.certificate,
+            Some(ClientCertificate::Pem {
+                bundle: bundle.into(),
+                password: None,
             })
-            .collect::<Vec<_>>();
-
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0][0].name, "Lobby");
+        );
     }
 
-    /// User state messages update cached users and emit events.
+    /// Unreadable client certificates fail as invalid configuration before dialing.
     #[test]
-    fn connect_applies_user_state() {
+    fn connect_rejects_invalid_client_certificate() {
         // Arrange
-        let config = MumbleConfig::new(
+        let mut config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
+        config.cert_pkcs12 = Some(vec![0, 1, 2].into());
         let capture = Rc::new(RefCell::new(None));
-        let messages = vec![ControlMessage::UserState {
-            id: 42,
-            name: "Alice".to_string(),
-            channel_id: 1,
-            muted: false,
-            deafened: false,
-            talking: true,
-        }];
-        let connector = TestControlConnectorWithMessages {
+        let connector = TestControlConnector {
             last_request: Rc::clone(&capture),
-            messages,
+            fail: false,
         };
         let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
 
         // Act
-        transport.connect().expect("connect failed");
+        let err = transport.connect().expect_err("expected connect to fail");
 
         // Assert
-        let events = transport
-            .take_events()
-            .into_iter()
-            .filter_map(|event| match event {
-                super::TransportEvent::Users(users) => Some(users),
-                _ => None,
-            })
-            .collect::<Vec<_>>();
-
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0][0].name, "Alice");
-        assert!(events[0][0].talking);
+        assert!(matches!(err, TransportError::InvalidConfig(_)));
+        assert!(capture.borrow().is_none());
+        assert_eq!(transport.conn_state(), ConnState::Disconnected);
     }
 
-    /// Server sync plus self user state updates the current channel id.
+    /// The TLS policy reaches the handshake, and invalid policies fail before dialing.
     #[test]
-    fn connect_sets_current_channel_for_self() {
+    fn connect_passes_and_validates_tls_policy() {
         // Arrange
-        let config = MumbleConfig::new(
+        let mut config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
-        let capture = Rc::new(RefCell::new(None));
-        let messages = vec![
-            ControlMessage::ServerSync { session: 7 },
-            ControlMessage::UserState {
-                id: 7,
-                name: "Self".to_string(),
-                channel_id: 2,
-                muted: false,
-                deafened: false,
-                talking: false,
-            },
-        ];
-        let connector = TestControlConnectorWithMessages {
-            last_request: Rc::clone(&capture),
-            messages,
+        config.tls = TlsPolicy {
+            min_version: TlsVersion::Tls13,
+            verify_system_cas: false,
+            ..TlsPolicy::default()
         };
-        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
-
-        // Act
-        transport.connect().expect("connect failed");
-
-        // Assert
+        let mut invalid = config.clone();
+        invalid.tls.cipher_list = Some("NOT-A-CIPHER".to_string());
+        let capture = Rc::new(RefCell::new(None));
+        let mut transport = MumbleTransport::with_connector(
+            config,
+            Box::new(TestControlConnector {
+                last_request: Rc::clone(&capture),
+                fail: false,
+            }),
+        );
+        let invalid_capture = Rc::new(RefCell::new(None));
+        let mut invalid_transport = MumbleTransport::with_connector(
+            invalid,
+            Box::new(TestControlConnector {
+                last_request: Rc::clone(&invalid_capture),
+                fail: false,
+            }),
+        );
+
+        // Act
+        transport.connect().expect("connect failed");
+        let err = invalid_transport
+            .connect()
+            .expect_err("expected connect to fail");
+
+        // Assert
+        let request = capture.borrow().clone().expect("missing request");
+        assert_eq!(request.tls.min_version, TlsVersion::Tls13);
+        assert!(!request.tls.verify_system_cas);
+        assert!(matches!(err, TransportError::InvalidConfig(_)));
+        assert!(invalid_capture.borrow().is_none());
+    }
+
+    /// Unknown server certificates emit an event the UI can use to ask for trust.
+    #[test]
+    fn connect_reports_untrusted_certificate() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let mut transport = MumbleTransport::with_connector(config, Box::new(TestPinningConnector));
+
+        // Act
+        let err = transport.connect().expect_err("expected connect to fail");
+
+        // Assert
+        assert!(matches!(err, TransportError::UntrustedCertificate(_)));
+        let events = transport.take_events();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            super::TransportEvent::UntrustedCertificate(certificate)
+                if certificate.fingerprint == "AA:BB" && certificate.pinned.is_none()
+        )));
+    }
+
+    /// A pinned fingerprint from the config is passed to the handshake.
+    #[test]
+    fn connect_forwards_trusted_fingerprint() {
+        // Arrange
+        let mut config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        config.trusted_fingerprint = Some("AA:BB".to_string());
+        let mut transport = MumbleTransport::with_connector(config, Box::new(TestPinningConnector));
+
+        // Act
+        let result = transport.connect();
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(transport.conn_state(), ConnState::Connected);
+    }
+
+    /// Server sync control messages update the stored session id.
+    #[test]
+    fn connect_applies_server_sync() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let capture = Rc::new(RefCell::new(None));
+        let messages = vec![ControlMessage::ServerSync { session: 42 }];
+        let connector = TestControlConnectorWithMessages {
+            last_request: Rc::clone(&capture),
+            messages,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+
+        // Act
+        transport.connect().expect("connect failed");
+
+        // Assert
+        assert_eq!(transport.session_id(), Some(42));
+    }
+
+    /// TLS session details are kept while connected, announced once and cleared on disconnect.
+    #[test]
+    fn connect_records_tls_session() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let tls = TlsSessionInfo {
+            version: "TLSv1.3".to_string(),
+            cipher: "TLS_AES_256_GCM_SHA384".to_string(),
+            chain: vec![CertificateInfo {
+                subject: "CN=voice.example".to_string(),
+                issuer: "CN=voice.example".to_string(),
+                not_before: "Jan  1 00:00:00 2026 GMT".to_string(),
+                not_after: "Jan  1 00:00:00 2027 GMT".to_string(),
+                fingerprint: "AA:BB".to_string(),
+            }],
+            fingerprint: "AA:BB".to_string(),
+        };
+        let connector = TestTlsConnector { tls: tls.clone() };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+
+        // Act
+        transport.connect().expect("connect failed");
+        let connected = transport.tls_session().cloned();
+        let events = transport.take_events();
+        transport.disconnect().expect("disconnect failed");
+
+        // Assert
+        assert_eq!(connected, Some(tls.clone()));
+        assert!(matches!(
+            events.as_slice(),
+            [
+                super::TransportEvent::ConnectionState(ConnState::Connecting),
+                super::TransportEvent::TlsSession(event_tls),
+                super::TransportEvent::ConnectionState(ConnState::Connected),
+            ] if *event_tls == tls
+        ));
+        assert!(transport.tls_session().is_none());
+    }
+
+    /// Diagnostics while connected check the path but do not log in over the live session.
+    #[test]
+    fn diagnose_skips_authenticate_while_connected() {
+        // Arrange
+        let server = stand_in_server();
+        let mut config =
+            MumbleConfig::new("127.0.0.1".to_string(), server.port, "alice".to_string());
+        config.trusted_fingerprint = Some(server.fingerprint.clone());
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![ControlMessage::ServerSync { session: 7 }],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+
+        // Act
+        let report = transport.diagnose();
+        let received = server.control.join().expect("control panicked");
+        server.udp.join().expect("udp panicked");
+
+        // Assert
+        assert!(matches!(
+            report
+                .stage(DiagnosticStage::Authenticate)
+                .map(|stage| &stage.status),
+            Some(StageStatus::Skipped(_))
+        ));
+        assert_eq!(
+            report
+                .stage(DiagnosticStage::TlsHandshake)
+                .map(|stage| &stage.status),
+            Some(&StageStatus::Passed)
+        );
+        assert!(received.is_empty());
+        assert_eq!(transport.conn_state(), ConnState::Connected);
+    }
+
+    /// CryptSetup opens the UDP voice channel, resyncs on a new server nonce and answers nonce requests.
+    #[test]
+    fn crypt_setup_opens_voice_channel() {
+        // Arrange
+        let server = UdpSocket::bind("127.0.0.1:0").expect("bind failed");
+        server
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .expect("timeout failed");
+        let port = server.local_addr().expect("missing address").port();
+        let config = MumbleConfig::new("127.0.0.1".to_string(), port, "tester".to_string());
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let crypt_setups = Rc::clone(&session.crypt_setups);
+        let incoming = Rc::clone(&session.incoming);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![
+                ControlMessage::CryptSetup {
+                    key: Some(SecretBytes::from(KEY.to_vec())),
+                    client_nonce: Some(vec![1; 16]),
+                    server_nonce: Some(vec![2; 16]),
+                },
+                ControlMessage::ServerSync { session: 7 },
+            ],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        incoming.borrow_mut().push(Ok(ControlMessage::CryptSetup {
+            key: None,
+            client_nonce: None,
+            server_nonce: Some(vec![3; 16]),
+        }));
+        incoming.borrow_mut().push(Ok(ControlMessage::CryptSetup {
+            key: None,
+            client_nonce: None,
+            server_nonce: None,
+        }));
+        let mut server_crypt = ServerCryptState::new_from(KEY, [3; 16], [1; 16]);
+
+        // Act
+        transport.poll().expect("poll failed");
+        let mut buffer = [0u8; 1024];
+        let (len, client) = server.recv_from(&mut buffer).expect("no udp ping");
+        let ping = server_crypt
+            .decrypt(&mut BytesMut::from(&buffer[..len]))
+            .expect("decrypt failed")
+            .expect("parse failed");
+        let mut sealed = BytesMut::new();
+        server_crypt.encrypt(audio(5, 1), &mut sealed);
+        server.send_to(&sealed, client).expect("send failed");
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut packets = Vec::new();
+        while packets.is_empty() && Instant::now() < deadline {
+            transport.poll().expect("poll failed");
+            packets = transport.take_voice_packets();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let stats = transport.voice_stats().expect("missing voice channel");
+        transport.disconnect().expect("disconnect failed");
+
+        // Assert
+        assert!(matches!(ping, VoicePacket::Ping { .. }));
+        assert_eq!(packets, vec![audio(5, 1)]);
+        assert_eq!((stats.good, stats.resync), (1, 1));
+        assert_eq!(*crypt_setups.borrow(), vec![Some(vec![1; 16])]);
+        assert!(transport.voice_stats().is_none());
+    }
+
+    /// Without a known control peer, voice stays tunnelled instead of resolving the host again.
+    #[test]
+    fn crypt_setup_without_peer_stays_on_tunnel() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.invalid".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![
+                ControlMessage::CryptSetup {
+                    key: Some(SecretBytes::from(KEY.to_vec())),
+                    client_nonce: Some(vec![1; 16]),
+                    server_nonce: Some(vec![2; 16]),
+                },
+                ControlMessage::ServerSync { session: 7 },
+            ],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+
+        // Act
+        transport.connect().expect("connect failed");
+
+        // Assert
+        assert_eq!(transport.voice_path(), Some(VoicePath::Tunnel));
+        assert!(transport.voice_stats().is_none());
+        assert!(!transport
+            .take_events()
+            .iter()
+            .any(|event| matches!(event, super::TransportEvent::Error(_))));
+    }
+
+    struct UdpVoiceServer {
+        socket: UdpSocket,
+        crypt: ServerCryptState,
+        client: Option<std::net::SocketAddr>,
+    }
+
+    impl UdpVoiceServer {
+        fn bind() -> Self {
+            let socket = UdpSocket::bind("127.0.0.1:0").expect("bind failed");
+            socket.set_nonblocking(true).expect("nonblocking failed");
+            Self {
+                socket,
+                crypt: ServerCryptState::new_from(KEY, [2; 16], [1; 16]),
+                client: None,
+            }
+        }
+
+        fn port(&self) -> u16 {
+            self.socket.local_addr().expect("missing address").port()
+        }
+
+        fn receive(&mut self, echo_pings: bool) -> Vec<VoicePacket<Serverbound>> {
+            let mut received = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while let Ok((len, client)) = self.socket.recv_from(&mut buffer) {
+                self.client = Some(client);
+                let Ok(Ok(packet)) = self.crypt.decrypt(&mut BytesMut::from(&buffer[..len])) else {
+                    continue;
+                };
+                match packet {
+                    VoicePacket::Ping { timestamp } if echo_pings => {
+                        let mut echo = BytesMut::new();
+                        self.crypt
+                            .encrypt(VoicePacket::Ping { timestamp }, &mut echo);
+                        self.socket.send_to(&echo, client).expect("echo failed");
+                    }
+                    VoicePacket::Ping { .. } => {}
+                    packet => received.push(packet),
+                }
+            }
+            received
+        }
+    }
+
+    fn udp_voice_transport(port: u16) -> (MumbleTransport, TestControlSession) {
+        let mut config = MumbleConfig::new("127.0.0.1".to_string(), port, "tester".to_string());
+        config.keepalive.interval = Duration::from_millis(50);
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let handle = TestControlSession {
+            commands: Rc::clone(&session.commands),
+            texts: Rc::clone(&session.texts),
+            closed: Rc::clone(&session.closed),
+            tokens: Rc::clone(&session.tokens),
+            crypt_setups: Rc::clone(&session.crypt_setups),
+            voices: Rc::clone(&session.voices),
+            incoming: Rc::clone(&session.incoming),
+            fail: false,
+        };
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![
+                ControlMessage::CryptSetup {
+                    key: Some(SecretBytes::from(KEY.to_vec())),
+                    client_nonce: Some(vec![1; 16]),
+                    server_nonce: Some(vec![2; 16]),
+                },
+                ControlMessage::ServerSync { session: 7 },
+            ],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        (transport, handle)
+    }
+
+    fn poll_until_path(
+        transport: &mut MumbleTransport,
+        server: &mut UdpVoiceServer,
+        echo_pings: bool,
+        path: VoicePath,
+    ) {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while transport.voice_path() != Some(path) {
+            assert!(
+                Instant::now() < deadline,
+                "voice never switched to {path:?}"
+            );
+            transport.poll().expect("poll failed");
+            server.receive(echo_pings);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn outgoing_audio(seq_num: u64) -> VoicePacket<Serverbound> {
+        VoicePacket::Audio {
+            _dst: std::marker::PhantomData,
+            target: 0,
+            session_id: (),
+            seq_num,
+            payload: VoicePacketPayload::Opus(bytes::Bytes::from_static(b"frame"), false),
+            position_info: None,
+        }
+    }
+
+    /// Voice starts tunnelled, moves to UDP once pings echo and falls back when they stop.
+    #[test]
+    fn voice_path_follows_udp_reachability() {
+        // Arrange
+        let mut server = UdpVoiceServer::bind();
+        let (mut transport, session) = udp_voice_transport(server.port());
+        let initial = transport.voice_path();
+        transport
+            .send_voice(outgoing_audio(1))
+            .expect("tunnel send failed");
+
+        // Act
+        poll_until_path(&mut transport, &mut server, true, VoicePath::Udp);
+        transport
+            .send_voice(outgoing_audio(2))
+            .expect("udp send failed");
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut over_udp = Vec::new();
+        while over_udp.is_empty() && Instant::now() < deadline {
+            over_udp = server.receive(true);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        poll_until_path(&mut transport, &mut server, false, VoicePath::Tunnel);
+        let paths: Vec<_> = transport
+            .take_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                super::TransportEvent::VoicePath(path) => Some(path),
+                _ => None,
+            })
+            .collect();
+
+        // Assert
+        assert_eq!(initial, Some(VoicePath::Tunnel));
+        assert_eq!(*session.voices.borrow(), vec![outgoing_audio(1)]);
+        assert_eq!(over_udp, vec![outgoing_audio(2)]);
+        assert_eq!(
+            paths,
+            [VoicePath::Tunnel, VoicePath::Udp, VoicePath::Tunnel]
+        );
+    }
+
+    /// Forcing TCP keeps voice on the tunnel even while UDP works, and tunnelled audio is received.
+    #[test]
+    fn force_tcp_voice_pins_tunnel() {
+        // Arrange
+        let mut server = UdpVoiceServer::bind();
+        let (mut transport, session) = udp_voice_transport(server.port());
+        poll_until_path(&mut transport, &mut server, true, VoicePath::Udp);
+        session
+            .incoming
+            .borrow_mut()
+            .push(Ok(ControlMessage::Voice(audio(4, 1))));
+
+        // Act
+        transport.set_force_tcp_voice(true);
+        let forced = transport.voice_path();
+        transport.poll().expect("poll failed");
+        transport
+            .send_voice(outgoing_audio(3))
+            .expect("send failed");
+        let received = transport.take_voice_packets();
+        transport.set_force_tcp_voice(false);
+
+        // Assert
+        assert_eq!(forced, Some(VoicePath::Tunnel));
+        assert_eq!(transport.voice_path(), Some(VoicePath::Udp));
+        assert_eq!(received, vec![audio(4, 1)]);
+        assert_eq!(*session.voices.borrow(), vec![outgoing_audio(3)]);
+    }
+
+    /// Sending voice without a crypt setup fails instead of silently dropping audio.
+    #[test]
+    fn send_voice_requires_voice_channel() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let mut transport =
+            MumbleTransport::with_connector(config, Box::new(TestControlConnector::default()));
+        let packet = VoicePacket::Ping { timestamp: 1 };
+
+        // Act
+        let disconnected = transport.send_voice(packet.clone());
+        transport.connect().expect("connect failed");
+        let unavailable = transport.send_voice(packet);
+
+        // Assert
+        assert!(matches!(disconnected, Err(TransportError::Disconnected)));
+        assert!(matches!(unavailable, Err(TransportError::Protocol(_))));
+    }
+
+    /// Server version messages are stored and announced before the connection completes.
+    #[test]
+    fn connect_records_server_info() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let info = ServerInfo {
+            version: Some(ProtocolVersion::new(1, 5, 0)),
+            release: Some("1.5.0".to_string()),
+            os: Some("Linux".to_string()),
+            os_version: None,
+        };
+        let connector = TestControlConnectorWithMessages {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![ControlMessage::ServerVersion(info.clone())],
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+
+        // Act
+        transport.connect().expect("connect failed");
+
+        // Assert
+        assert_eq!(transport.server_info(), Some(&info));
+        assert!(matches!(
+            transport.take_events().as_slice(),
+            [
+                super::TransportEvent::ConnectionState(ConnState::Connecting),
+                super::TransportEvent::ServerInfo(event_info),
+                super::TransportEvent::ConnectionState(ConnState::Connected),
+            ] if *event_info == info
+        ));
+    }
+
+    /// Channel state messages update cached channels and emit events.
+    #[test]
+    fn connect_applies_channel_state() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let capture = Rc::new(RefCell::new(None));
+        let messages = vec![ControlMessage::ChannelState(ChannelStateUpdate {
+            id: 1,
+            name: Some("Lobby".to_string()),
+            parent_id: None,
+        })];
+        let connector = TestControlConnectorWithMessages {
+            last_request: Rc::clone(&capture),
+            messages,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+
+        // Act
+        transport.connect().expect("connect failed");
+
+        // Assert
+        let events = transport
+            .take_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                super::TransportEvent::Channels(channels) => Some(channels),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0][0].name, "Lobby");
+    }
+
+    /// User state messages update cached users and emit events.
+    #[test]
+    fn connect_applies_user_state() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let capture = Rc::new(RefCell::new(None));
+        let messages = vec![ControlMessage::UserState(UserStateUpdate {
+            id: 42,
+            name: Some("Alice".to_string()),
+            channel_id: Some(1),
+            muted: Some(false),
+            deafened: Some(false),
+            talking: Some(true),
+            ..Default::default()
+        })];
+        let connector = TestControlConnectorWithMessages {
+            last_request: Rc::clone(&capture),
+            messages,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+
+        // Act
+        transport.connect().expect("connect failed");
+
+        // Assert
+        let events = transport
+            .take_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                super::TransportEvent::Users(users) => Some(users),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0][0].name, "Alice");
+        assert!(events[0][0].talking);
+    }
+
+    /// Partial user deltas after sync only change the fields they carry.
+    #[test]
+    fn poll_merges_partial_user_state() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let incoming = Rc::clone(&session.incoming);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![
+                ControlMessage::UserState(UserStateUpdate {
+                    id: 7,
+                    name: Some("Self".to_string()),
+                    channel_id: Some(1),
+                    muted: Some(false),
+                    deafened: Some(true),
+                    talking: Some(false),
+                    ..Default::default()
+                }),
+                ControlMessage::ServerSync { session: 7 },
+            ],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+        incoming
+            .borrow_mut()
+            .push(Ok(ControlMessage::UserState(UserStateUpdate {
+                id: 7,
+                name: None,
+                channel_id: None,
+                muted: Some(true),
+                deafened: None,
+                talking: None,
+                ..Default::default()
+            })));
+
+        // Act
+        transport.poll().expect("poll failed");
+
+        // Assert
+        assert_eq!(transport.current_channel_id(), Some(1));
+        let events = transport.take_events();
+        let [super::TransportEvent::Users(users)] = events.as_slice() else {
+            panic!("expected a single users event");
+        };
+        assert_eq!(users[0].name, "Self");
+        assert_eq!(users[0].channel_id, 1);
+        assert!(users[0].muted);
+        assert!(users[0].deafened);
+    }
+
+    /// Server sync plus self user state updates the current channel id.
+    #[test]
+    fn connect_sets_current_channel_for_self() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let capture = Rc::new(RefCell::new(None));
+        let messages = vec![
+            ControlMessage::UserState(UserStateUpdate {
+                id: 7,
+                name: Some("Self".to_string()),
+                channel_id: Some(2),
+                muted: Some(false),
+                deafened: Some(false),
+                talking: Some(false),
+                ..Default::default()
+            }),
+            ControlMessage::ServerSync { session: 7 },
+        ];
+        let connector = TestControlConnectorWithMessages {
+            last_request: Rc::clone(&capture),
+            messages,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+
+        // Act
+        transport.connect().expect("connect failed");
+
+        // Assert
+        assert_eq!(transport.current_channel_id(), Some(2));
+    }
+
+    /// Join fails when the transport is disconnected.
+    #[test]
+    fn join_channel_rejects_when_disconnected() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let mut transport = MumbleTransport::new(config);
+
+        // Act
+        let err = transport
+            .join_channel(1)
+            .expect_err("expected join to fail");
+        // Assert
+        assert!(matches!(err, TransportError::Disconnected));
+    }
+
+    /// Join fails when session id is missing after connection.
+    #[test]
+    fn join_channel_rejects_missing_session() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let mut transport = MumbleTransport::new(config);
+        transport.connect().expect("connect failed");
+        transport.take_events();
+
+        // Act
+        let err = transport
+            .join_channel(1)
+            .expect_err("expected join to fail");
+        // Assert
+        assert!(matches!(err, TransportError::Protocol(_)));
+        assert!(transport.take_events().is_empty());
+    }
+
+    /// Join fails when the target channel is not in the cache.
+    #[test]
+    fn join_channel_rejects_unknown_channel() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let capture = Rc::new(RefCell::new(None));
+        let messages = vec![
+            ControlMessage::UserState(UserStateUpdate {
+                id: 42,
+                name: Some("Self".to_string()),
+                channel_id: Some(1),
+                muted: Some(false),
+                deafened: Some(false),
+                talking: Some(false),
+                ..Default::default()
+            }),
+            ControlMessage::ChannelState(ChannelStateUpdate {
+                id: 1,
+                name: Some("Lobby".to_string()),
+                parent_id: None,
+            }),
+            ControlMessage::ServerSync { session: 42 },
+        ];
+        let connector = TestControlConnectorWithMessages {
+            last_request: Rc::clone(&capture),
+            messages,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+        transport.take_events();
+
+        // Act
+        let err = transport
+            .join_channel(99)
+            .expect_err("expected join to fail");
+        // Assert
+        assert!(matches!(err, TransportError::Protocol(_)));
+        assert!(transport.take_events().is_empty());
+    }
+
+    /// Join fails when the self user state is missing in the cache.
+    #[test]
+    fn join_channel_rejects_missing_self_user() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let capture = Rc::new(RefCell::new(None));
+        let messages = vec![
+            ControlMessage::ChannelState(ChannelStateUpdate {
+                id: 2,
+                name: Some("Ops".to_string()),
+                parent_id: None,
+            }),
+            ControlMessage::ServerSync { session: 7 },
+        ];
+        let connector = TestControlConnectorWithMessages {
+            last_request: Rc::clone(&capture),
+            messages,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+
+        // Act
+        let err = transport
+            .join_channel(2)
+            .expect_err("expected join to fail");
+        // Assert
+        assert!(matches!(err, TransportError::Protocol(_)));
+        assert!(transport.take_events().is_empty());
+    }
+
+    /// Join updates the cached self user channel and emits a user snapshot.
+    #[test]
+    fn join_channel_updates_self_channel() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let capture = Rc::new(RefCell::new(None));
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let messages = vec![
+            ControlMessage::UserState(UserStateUpdate {
+                id: 7,
+                name: Some("Self".to_string()),
+                channel_id: Some(1),
+                muted: Some(false),
+                deafened: Some(false),
+                talking: Some(false),
+                ..Default::default()
+            }),
+            ControlMessage::ChannelState(ChannelStateUpdate {
+                id: 1,
+                name: Some("Lobby".to_string()),
+                parent_id: None,
+            }),
+            ControlMessage::ChannelState(ChannelStateUpdate {
+                id: 2,
+                name: Some("Ops".to_string()),
+                parent_id: None,
+            }),
+            ControlMessage::ServerSync { session: 7 },
+        ];
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::clone(&capture),
+            messages,
+            session: TestControlSession::new(Rc::clone(&commands)),
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+
+        // Act
+        transport.join_channel(2).expect("join failed");
+
+        // Assert
         assert_eq!(transport.current_channel_id(), Some(2));
+        let users_events = transport
+            .take_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                super::TransportEvent::Users(users) => Some(users),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(users_events.len(), 2);
+        assert_eq!(users_events[0][0].channel_id, 1);
+        assert_eq!(users_events[1][0].channel_id, 2);
+    }
+
+    /// Join fails when the control session is not available.
+    #[test]
+    fn join_channel_rejects_missing_control_session() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let capture = Rc::new(RefCell::new(None));
+        let messages = vec![
+            ControlMessage::UserState(UserStateUpdate {
+                id: 7,
+                name: Some("Self".to_string()),
+                channel_id: Some(1),
+                muted: Some(false),
+                deafened: Some(false),
+                talking: Some(false),
+                ..Default::default()
+            }),
+            ControlMessage::ChannelState(ChannelStateUpdate {
+                id: 2,
+                name: Some("Ops".to_string()),
+                parent_id: None,
+            }),
+            ControlMessage::ServerSync { session: 7 },
+        ];
+        let connector = TestControlConnectorWithMessages {
+            last_request: Rc::clone(&capture),
+            messages,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+
+        // Act
+        let err = transport
+            .join_channel(2)
+            .expect_err("expected join to fail");
+        // Assert
+        assert!(matches!(err, TransportError::Protocol(_)));
+        assert!(transport.take_events().is_empty());
+    }
+
+    /// Join sends the expected user state command to the control session.
+    #[test]
+    fn join_channel_sends_user_state_command() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let capture = Rc::new(RefCell::new(None));
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let messages = vec![
+            ControlMessage::UserState(UserStateUpdate {
+                id: 7,
+                name: Some("Self".to_string()),
+                channel_id: Some(1),
+                muted: Some(false),
+                deafened: Some(false),
+                talking: Some(false),
+                ..Default::default()
+            }),
+            ControlMessage::ChannelState(ChannelStateUpdate {
+                id: 1,
+                name: Some("Lobby".to_string()),
+                parent_id: None,
+            }),
+            ControlMessage::ChannelState(ChannelStateUpdate {
+                id: 2,
+                name: Some("Ops".to_string()),
+                parent_id: None,
+            }),
+            ControlMessage::ServerSync { session: 7 },
+        ];
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::clone(&capture),
+            messages,
+            session: TestControlSession::new(Rc::clone(&commands)),
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+
+        // Act
+        transport.join_channel(2).expect("join failed");
+
+        // Assert
+        let commands = commands.borrow();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(
+            commands[0],
+            UserStateCommand {
+                session_id: 7,
+                channel_id: Some(2),
+                muted: None,
+                deafened: None,
+            }
+        );
+    }
+
+    type IncomingQueue = Rc<RefCell<Vec<Result<ControlMessage, TransportError>>>>;
+
+    fn connected_self(
+        muted: bool,
+        deafened: bool,
+    ) -> (
+        MumbleTransport,
+        Rc<RefCell<Vec<UserStateCommand>>>,
+        IncomingQueue,
+    ) {
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let session = TestControlSession::new(Rc::clone(&commands));
+        let incoming = Rc::clone(&session.incoming);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![
+                ControlMessage::UserState(UserStateUpdate {
+                    id: 7,
+                    name: Some("Self".to_string()),
+                    channel_id: Some(1),
+                    muted: Some(muted),
+                    deafened: Some(deafened),
+                    ..Default::default()
+                }),
+                ControlMessage::ServerSync { session: 7 },
+            ],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+        (transport, commands, incoming)
+    }
+
+    fn self_command(muted: bool, deafened: bool) -> UserStateCommand {
+        UserStateCommand {
+            session_id: 7,
+            channel_id: None,
+            muted: Some(muted),
+            deafened: Some(deafened),
+        }
+    }
+
+    /// Self mute sends the flag and updates the local user immediately.
+    #[test]
+    fn set_self_mute_updates_optimistically() {
+        // Arrange
+        let (mut transport, commands, _) = connected_self(false, false);
+
+        // Act
+        transport.set_self_mute(true).expect("mute failed");
+
+        // Assert
+        assert_eq!(commands.borrow().as_slice(), [self_command(true, false)]);
+        let events = transport.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [super::TransportEvent::Users(users)] if users[0].muted && !users[0].deafened
+        ));
+    }
+
+    /// Deafening also mutes, and undeafening restores the unmuted state.
+    #[test]
+    fn set_self_deafen_restores_previous_mute() {
+        // Arrange
+        let (mut transport, commands, _) = connected_self(false, false);
+
+        // Act
+        transport.set_self_deafen(true).expect("deafen failed");
+        transport.set_self_deafen(false).expect("undeafen failed");
+
+        // Assert
+        assert_eq!(
+            commands.borrow().as_slice(),
+            [self_command(true, true), self_command(false, false)]
+        );
     }
 
-    /// Join fails when the transport is disconnected.
+    /// Undeafening keeps an explicit mute that was set before deafening.
     #[test]
-    fn join_channel_rejects_when_disconnected() {
+    fn set_self_deafen_keeps_explicit_mute() {
+        // Arrange
+        let (mut transport, commands, _) = connected_self(true, false);
+
+        // Act
+        transport.set_self_deafen(true).expect("deafen failed");
+        transport.set_self_deafen(false).expect("undeafen failed");
+
+        // Assert
+        assert_eq!(
+            commands.borrow().as_slice(),
+            [self_command(true, true), self_command(true, false)]
+        );
+    }
+
+    /// Unmuting while deafened undeafens as well.
+    #[test]
+    fn set_self_mute_off_undeafens() {
+        // Arrange
+        let (mut transport, commands, _) = connected_self(false, false);
+        transport.set_self_deafen(true).expect("deafen failed");
+
+        // Act
+        transport.set_self_mute(false).expect("unmute failed");
+        transport.set_self_deafen(true).expect("deafen failed");
+        transport.set_self_deafen(false).expect("undeafen failed");
+
+        // Assert
+        let commands = commands.borrow();
+        assert_eq!(commands[1], self_command(false, false));
+        assert_eq!(commands[3], self_command(false, false));
+    }
+
+    /// The server's echoed state wins over the optimistic update.
+    #[test]
+    fn poll_reconciles_self_flags_from_server() {
+        // Arrange
+        let (mut transport, _, incoming) = connected_self(false, false);
+        transport.set_self_mute(true).expect("mute failed");
+        transport.take_events();
+        incoming
+            .borrow_mut()
+            .push(Ok(ControlMessage::UserState(UserStateUpdate {
+                id: 7,
+                muted: Some(false),
+                deafened: Some(false),
+                ..Default::default()
+            })));
+
+        // Act
+        transport.poll().expect("poll failed");
+
+        // Assert
+        assert!(matches!(
+            transport.take_events().as_slice(),
+            [super::TransportEvent::Users(users)] if !users[0].muted
+        ));
+    }
+
+    /// Self flags require a connection and a known self user.
+    #[test]
+    fn set_self_flags_require_self_user() {
+        // Arrange
+        let config = MumbleConfig::new("server".to_string(), DEFAULT_PORT, "tester".to_string());
+        let mut disconnected = MumbleTransport::new(config);
+        let (mut connected, commands, _) = connected_self(false, false);
+        connected.session_id = Some(8);
+
+        // Act
+        let offline = disconnected.set_self_mute(true);
+        let unknown = connected.set_self_deafen(true);
+
+        // Assert
+        assert!(matches!(offline, Err(TransportError::Disconnected)));
+        assert!(matches!(unknown, Err(TransportError::Protocol(_))));
+        assert!(commands.borrow().is_empty());
+    }
+
+    /// Configured tokens go into the handshake, and runtime updates are sent and kept for reconnects.
+    #[test]
+    fn set_access_tokens_updates_session_and_config() {
+        // Arrange
+        let mut config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        config.tokens = vec!["red".into()];
+        let capture = Rc::new(RefCell::new(None));
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let sent_tokens = Rc::clone(&session.tokens);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::clone(&capture),
+            messages: vec![ControlMessage::ServerSync { session: 7 }],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        let handshake_tokens = capture.borrow().clone().expect("missing request").tokens;
+
+        // Act
+        transport
+            .set_access_tokens(vec!["red".into(), "blue".into()])
+            .expect("update failed");
+        transport.disconnect().expect("disconnect failed");
+        transport.connect().expect("reconnect failed");
+
+        // Assert
+        assert_eq!(handshake_tokens, vec!["red"]);
+        assert_eq!(*sent_tokens.borrow(), vec![vec!["red", "blue"]]);
+        assert_eq!(
+            capture.borrow().clone().expect("missing request").tokens,
+            vec!["red", "blue"]
+        );
+    }
+
+    /// Runtime token updates are saved to the credential vault for this server.
+    #[test]
+    fn set_access_tokens_persists_to_vault() {
         // Arrange
+        let dir = TempDir::new();
+        let key = VaultKey::Passphrase("secret".into());
+        let mut vault = CredentialVault::open(&dir.0, &key).expect("open failed");
+        vault
+            .set_credentials(
+                "voice.example",
+                DEFAULT_PORT,
+                Credentials {
+                    password: Some("hunter2".into()),
+                    tokens: vec!["red".into()],
+                },
+            )
+            .expect("save failed");
         let config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![ControlMessage::ServerSync { session: 7 }],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.set_credential_vault(vault);
+        transport.connect().expect("connect failed");
+
+        // Act
+        transport
+            .set_access_tokens(vec!["blue".into()])
+            .expect("update failed");
+
+        // Assert
+        let reopened = CredentialVault::open(&dir.0, &key).expect("reopen failed");
+        assert_eq!(
+            reopened.credentials("voice.example", DEFAULT_PORT),
+            Some(&Credentials {
+                password: Some("hunter2".into()),
+                tokens: vec!["blue".into()],
+            })
+        );
+    }
+
+    /// A token update that cannot be saved is not sent and keeps the configured tokens.
+    #[test]
+    fn set_access_tokens_sends_nothing_when_vault_save_fails() {
+        // Arrange
+        let dir = TempDir::new();
+        let vault_dir = dir.0.join("data");
+        let vault = CredentialVault::open(&vault_dir, &VaultKey::Passphrase("secret".into()))
+            .expect("open failed");
+        std::fs::write(&vault_dir, b"not a directory").expect("write failed");
+        let mut config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        config.tokens = vec!["red".into()];
+        let capture = Rc::new(RefCell::new(None));
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let sent_tokens = Rc::clone(&session.tokens);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::clone(&capture),
+            messages: vec![ControlMessage::ServerSync { session: 7 }],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.set_credential_vault(vault);
+        transport.connect().expect("connect failed");
+
+        // Act
+        let result = transport.set_access_tokens(vec!["blue".into()]);
+        transport.disconnect().expect("disconnect failed");
+        transport.connect().expect("reconnect failed");
+
+        // Assert
+        assert!(matches!(result, Err(TransportError::Io(_))));
+        assert!(sent_tokens.borrow().is_empty());
+        assert_eq!(
+            capture.borrow().clone().expect("missing request").tokens,
+            vec!["red"]
+        );
+    }
+
+    /// Token updates require a connection.
+    #[test]
+    fn set_access_tokens_rejects_when_disconnected() {
+        // Arrange
+        let config = MumbleConfig::new("server".to_string(), DEFAULT_PORT, "tester".to_string());
         let mut transport = MumbleTransport::new(config);
 
         // Act
         let err = transport
-            .join_channel(1)
-            .expect_err("expected join to fail");
+            .set_access_tokens(vec!["red".into()])
+            .expect_err("expected update to fail");
+
         // Assert
         assert!(matches!(err, TransportError::Disconnected));
     }
 
-    /// Join fails when session id is missing after connection.
+    /// Disconnect closes the session, clears cached state and emits the teardown sequence.
     #[test]
-    fn join_channel_rejects_missing_session() {
+    fn disconnect_tears_down_session() {
         // Arrange
         let config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
-        let mut transport = MumbleTransport::new(config);
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let closed = Rc::clone(&session.closed);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![
+                ControlMessage::ChannelState(ChannelStateUpdate {
+                    id: 1,
+                    name: Some("Lobby".to_string()),
+                    parent_id: None,
+                }),
+                ControlMessage::UserState(UserStateUpdate {
+                    id: 7,
+                    name: Some("Self".to_string()),
+                    channel_id: Some(1),
+                    ..Default::default()
+                }),
+                ControlMessage::ServerSync { session: 7 },
+            ],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
         transport.connect().expect("connect failed");
         transport.take_events();
 
         // Act
-        let err = transport
-            .join_channel(1)
-            .expect_err("expected join to fail");
+        transport.disconnect().expect("disconnect failed");
+
         // Assert
-        assert!(matches!(err, TransportError::Protocol(_)));
-        assert!(transport.take_events().is_empty());
+        assert!(*closed.borrow());
+        assert_eq!(transport.conn_state(), ConnState::Disconnected);
+        assert_eq!(transport.session_id(), None);
+        assert_eq!(transport.current_channel_id(), None);
+        assert!(transport.server_info().is_none());
+        assert!(transport.state.channels().is_empty());
+        assert!(transport.state.users().is_empty());
+        let events = transport.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                super::TransportEvent::Channels(channels),
+                super::TransportEvent::Users(users),
+                super::TransportEvent::ConnectionState(ConnState::Disconnected),
+            ] if channels.is_empty() && users.is_empty()
+        ));
+    }
+
+    /// The same transport can connect again after disconnecting.
+    #[test]
+    fn disconnect_allows_reconnect() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let capture = Rc::new(RefCell::new(None));
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::clone(&capture),
+            messages: vec![ControlMessage::ServerSync { session: 7 }],
+            session: TestControlSession::new(Rc::new(RefCell::new(Vec::new()))),
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.disconnect().expect("disconnect failed");
+        capture.borrow_mut().take();
+
+        // Act
+        transport.connect().expect("reconnect failed");
+
+        // Assert
+        assert!(capture.borrow().is_some());
+        assert_eq!(transport.conn_state(), ConnState::Connected);
+        assert_eq!(transport.session_id(), Some(7));
+    }
+
+    /// Disconnect resets an errored transport and is a no-op when already disconnected.
+    #[test]
+    fn disconnect_recovers_from_error_state() {
+        // Arrange
+        let config = MumbleConfig::new("server".to_string(), DEFAULT_PORT, "tester".to_string());
+        let mut idle = MumbleTransport::new(config.clone());
+        let mut failed = MumbleTransport::new(config);
+        failed.set_conn_state(ConnState::Error);
+        failed.take_events();
+
+        // Act
+        idle.disconnect().expect("disconnect failed");
+        failed.disconnect().expect("disconnect failed");
+
+        // Assert
+        assert!(idle.take_events().is_empty());
+        assert_eq!(failed.conn_state(), ConnState::Disconnected);
+    }
+
+    /// Poll applies messages that arrive after the handshake completed.
+    #[test]
+    fn poll_applies_messages_after_connect() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let incoming = Rc::clone(&session.incoming);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![ControlMessage::ServerSync { session: 7 }],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+        incoming
+            .borrow_mut()
+            .push(Ok(ControlMessage::UserState(UserStateUpdate {
+                id: 9,
+                name: Some("Bob".to_string()),
+                channel_id: Some(1),
+                muted: Some(false),
+                deafened: Some(false),
+                talking: Some(false),
+                ..Default::default()
+            })));
+
+        // Act
+        transport.poll().expect("poll failed");
+
+        // Assert
+        let events = transport.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [super::TransportEvent::Users(users)] if users[0].name == "Bob"
+        ));
+        assert!(incoming.borrow().is_empty());
+    }
+
+    /// Ping replies are reported as latency events.
+    #[test]
+    fn poll_reports_latency() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let incoming = Rc::clone(&session.incoming);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: Vec::new(),
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+        incoming.borrow_mut().push(Ok(ControlMessage::Pong {
+            rtt: Duration::from_millis(42),
+        }));
+
+        // Act
+        transport.poll().expect("poll failed");
+
+        // Assert
+        assert!(matches!(
+            transport.take_events().as_slice(),
+            [super::TransportEvent::Latency(rtt)] if *rtt == Duration::from_millis(42)
+        ));
+    }
+
+    /// A missed keepalive deadline moves the connection into the error state.
+    #[test]
+    fn poll_marks_error_on_keepalive_timeout() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let incoming = Rc::clone(&session.incoming);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: Vec::new(),
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+        incoming.borrow_mut().push(Err(TransportError::Timeout));
+
+        // Act
+        let err = transport.poll().expect_err("expected poll to fail");
+
+        // Assert
+        assert!(matches!(err, TransportError::Timeout));
+        assert_eq!(transport.conn_state(), ConnState::Error);
+    }
+
+    /// Other users leaving are dropped from the cache.
+    #[test]
+    fn poll_removes_other_users() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let incoming = Rc::clone(&session.incoming);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![
+                ControlMessage::UserState(UserStateUpdate {
+                    id: 9,
+                    name: Some("Bob".to_string()),
+                    channel_id: Some(1),
+                    muted: Some(false),
+                    deafened: Some(false),
+                    talking: Some(false),
+                    ..Default::default()
+                }),
+                ControlMessage::ServerSync { session: 7 },
+            ],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+        incoming.borrow_mut().push(Ok(ControlMessage::UserRemove {
+            id: 9,
+            actor_id: Some(9),
+            reason: None,
+            ban: false,
+        }));
+
+        // Act
+        transport.poll().expect("poll failed");
+
+        // Assert
+        assert!(matches!(
+            transport.take_events().as_slice(),
+            [super::TransportEvent::Users(users)] if users.is_empty()
+        ));
+        assert_eq!(transport.conn_state(), ConnState::Connected);
+    }
+
+    /// Removing our own session reports the ban and disconnects.
+    #[test]
+    fn poll_reports_own_ban_and_disconnects() {
+        // Arrange
+        let config = MumbleConfig::new(
+            "voice.example".to_string(),
+            DEFAULT_PORT,
+            "tester".to_string(),
+        );
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let incoming = Rc::clone(&session.incoming);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![
+                ControlMessage::ChannelState(ChannelStateUpdate {
+                    id: 1,
+                    name: Some("Lobby".to_string()),
+                    parent_id: None,
+                }),
+                ControlMessage::UserState(UserStateUpdate {
+                    id: 7,
+                    name: Some("Self".to_string()),
+                    channel_id: Some(1),
+                    ..Default::default()
+                }),
+                ControlMessage::ServerSync { session: 7 },
+            ],
+            session,
+        };
+        let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
+        transport.connect().expect("connect failed");
+        transport.take_events();
+        incoming.borrow_mut().push(Ok(ControlMessage::UserRemove {
+            id: 7,
+            actor_id: Some(1),
+            reason: Some("bye".to_string()),
+            ban: true,
+        }));
+        incoming
+            .borrow_mut()
+            .push(Err(TransportError::Disconnected));
+
+        // Act
+        transport.poll().expect("poll failed");
+
+        // Assert
+        assert_eq!(transport.conn_state(), ConnState::Disconnected);
+        let events = transport.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                super::TransportEvent::Removed(RemovalNotice {
+                    kind: RemovalKind::Ban,
+                    actor_id: Some(1),
+                    reason: Some(_),
+                }),
+                super::TransportEvent::Channels(channels),
+                super::TransportEvent::Users(users),
+                super::TransportEvent::ConnectionState(ConnState::Disconnected),
+            ] if channels.is_empty() && users.is_empty()
+        ));
+        assert_eq!(transport.session_id(), None);
+        assert_eq!(transport.current_channel_id(), None);
     }
 
-    /// Join fails when the target channel is not in the cache.
+    /// Channel removals drop the subtree and move our own user with the server.
     #[test]
-    fn join_channel_rejects_unknown_channel() {
+    fn poll_applies_channel_remove() {
         // Arrange
         let config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
-        let capture = Rc::new(RefCell::new(None));
-        let messages = vec![
-            ControlMessage::ServerSync { session: 42 },
-            ControlMessage::UserState {
-                id: 42,
-                name: "Self".to_string(),
-                channel_id: 1,
-                muted: false,
-                deafened: false,
-                talking: false,
-            },
-            ControlMessage::ChannelState {
-                id: 1,
-                name: "Lobby".to_string(),
-                parent_id: None,
-            },
-        ];
-        let connector = TestControlConnectorWithMessages {
-            last_request: Rc::clone(&capture),
-            messages,
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let incoming = Rc::clone(&session.incoming);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: vec![
+                ControlMessage::ChannelState(ChannelStateUpdate {
+                    id: 0,
+                    name: Some("Root".to_string()),
+                    parent_id: None,
+                }),
+                ControlMessage::ChannelState(ChannelStateUpdate {
+                    id: 3,
+                    name: Some("Temp".to_string()),
+                    parent_id: Some(0),
+                }),
+                ControlMessage::UserState(UserStateUpdate {
+                    id: 7,
+                    name: Some("Self".to_string()),
+                    channel_id: Some(3),
+                    muted: Some(false),
+                    deafened: Some(false),
+                    talking: Some(false),
+                    ..Default::default()
+                }),
+                ControlMessage::ServerSync { session: 7 },
+            ],
+            session,
         };
         let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
         transport.connect().expect("connect failed");
         transport.take_events();
-        transport.take_events();
+        incoming
+            .borrow_mut()
+            .push(Ok(ControlMessage::ChannelRemove { id: 3 }));
 
         // Act
-        let err = transport
-            .join_channel(99)
-            .expect_err("expected join to fail");
+        transport.poll().expect("poll failed");
+
         // Assert
-        assert!(matches!(err, TransportError::Protocol(_)));
-        assert!(transport.take_events().is_empty());
+        assert_eq!(transport.current_channel_id(), Some(0));
+        assert!(matches!(
+            transport.take_events().as_slice(),
+            [
+                super::TransportEvent::Channels(channels),
+                super::TransportEvent::Users(_),
+            ] if channels.len() == 1
+        ));
     }
 
-    /// Join fails when the self user state is missing in the cache.
+    /// Poll moves back to disconnected when the server closes the session.
     #[test]
-    fn join_channel_rejects_missing_self_user() {
+    fn poll_disconnects_when_session_closes() {
         // Arrange
         let config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
-        let capture = Rc::new(RefCell::new(None));
-        let messages = vec![
-            ControlMessage::ServerSync { session: 7 },
-            ControlMessage::ChannelState {
-                id: 2,
-                name: "Ops".to_string(),
-                parent_id: None,
-            },
-        ];
-        let connector = TestControlConnectorWithMessages {
-            last_request: Rc::clone(&capture),
-            messages,
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let incoming = Rc::clone(&session.incoming);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
+            messages: Vec::new(),
+            session,
         };
         let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
         transport.connect().expect("connect failed");
         transport.take_events();
+        incoming
+            .borrow_mut()
+            .push(Err(TransportError::Disconnected));
 
         // Act
-        let err = transport
-            .join_channel(2)
-            .expect_err("expected join to fail");
+        let err = transport.poll().expect_err("expected poll to fail");
+
         // Assert
-        assert!(matches!(err, TransportError::Protocol(_)));
-        assert!(transport.take_events().is_empty());
+        assert!(matches!(err, TransportError::Disconnected));
+        assert_eq!(transport.conn_state(), ConnState::Disconnected);
+        assert!(matches!(
+            transport.take_events().as_slice(),
+            [super::TransportEvent::ConnectionState(
+                ConnState::Disconnected
+            )]
+        ));
     }
 
-    /// Join updates the cached self user channel and emits a user snapshot.
+    /// Poll reports reader loop failures as errors.
     #[test]
-    fn join_channel_updates_self_channel() {
+    fn poll_reports_session_errors() {
         // Arrange
         let config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
-        let capture = Rc::new(RefCell::new(None));
-        let commands = Rc::new(RefCell::new(Vec::new()));
-        let messages = vec![
-            ControlMessage::ServerSync { session: 7 },
-            ControlMessage::UserState {
-                id: 7,
-                name: "Self".to_string(),
-                channel_id: 1,
-                muted: false,
-                deafened: false,
-                talking: false,
-            },
-            ControlMessage::ChannelState {
-                id: 1,
-                name: "Lobby".to_string(),
-                parent_id: None,
-            },
-            ControlMessage::ChannelState {
-                id: 2,
-                name: "Ops".to_string(),
-                parent_id: None,
-            },
-        ];
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let incoming = Rc::clone(&session.incoming);
         let connector = TestControlConnectorWithSession {
-            last_request: Rc::clone(&capture),
-            messages,
-            session: TestControlSession::new(Rc::clone(&commands)),
+            last_request: Rc::new(RefCell::new(None)),
+            messages: Vec::new(),
+            session,
         };
         let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
         transport.connect().expect("connect failed");
+        transport.take_events();
+        incoming
+            .borrow_mut()
+            .push(Err(TransportError::Io("reset".to_string())));
 
         // Act
-        transport.join_channel(2).expect("join failed");
+        let err = transport.poll().expect_err("expected poll to fail");
 
         // Assert
-        assert_eq!(transport.current_channel_id(), Some(2));
-        let users_events = transport
-            .take_events()
-            .into_iter()
-            .filter_map(|event| match event {
-                super::TransportEvent::Users(users) => Some(users),
-                _ => None,
-            })
-            .collect::<Vec<_>>();
-        assert_eq!(users_events.len(), 2);
-        assert_eq!(users_events[0][0].channel_id, 1);
-        assert_eq!(users_events[1][0].channel_id, 2);
+        assert!(matches!(err, TransportError::Io(_)));
+        assert_eq!(transport.conn_state(), ConnState::Error);
+        assert!(matches!(
+            transport.take_events().as_slice(),
+            [
+                super::TransportEvent::ConnectionState(ConnState::Error),
+                super::TransportEvent::Error(_),
+            ]
+        ));
     }
 
-    /// Join fails when the control session is not available.
+    /// Poll is a no-op before connecting.
     #[test]
-    fn join_channel_rejects_missing_control_session() {
+    fn poll_is_noop_when_disconnected() {
         // Arrange
+        let config = MumbleConfig::new("server".to_string(), DEFAULT_PORT, "tester".to_string());
+        let mut transport = MumbleTransport::new(config);
+
+        // Act
+        transport.poll().expect("poll failed");
+
+        // Assert
+        assert!(transport.take_events().is_empty());
+    }
+
+    fn connected_with_session(
+        messages: Vec<ControlMessage>,
+    ) -> (MumbleTransport, Rc<RefCell<Vec<TextMessageCommand>>>) {
         let config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
-        let capture = Rc::new(RefCell::new(None));
-        let messages = vec![
-            ControlMessage::ServerSync { session: 7 },
-            ControlMessage::UserState {
-                id: 7,
-                name: "Self".to_string(),
-                channel_id: 1,
-                muted: false,
-                deafened: false,
-                talking: false,
-            },
-            ControlMessage::ChannelState {
-                id: 2,
-                name: "Ops".to_string(),
-                parent_id: None,
-            },
-        ];
-        let connector = TestControlConnectorWithMessages {
-            last_request: Rc::clone(&capture),
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let texts = Rc::clone(&session.texts);
+        let connector = TestControlConnectorWithSession {
+            last_request: Rc::new(RefCell::new(None)),
             messages,
+            session,
         };
         let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
         transport.connect().expect("connect failed");
         transport.take_events();
+        (transport, texts)
+    }
+
+    /// Text to the current channel targets the channel we are in and is echoed locally.
+    #[test]
+    fn send_text_targets_current_channel() {
+        // Arrange
+        let (mut transport, texts) = connected_with_session(vec![
+            ControlMessage::UserState(UserStateUpdate {
+                id: 7,
+                name: Some("Self".to_string()),
+                channel_id: Some(3),
+                ..Default::default()
+            }),
+            ControlMessage::ServerSync { session: 7 },
+        ]);
 
         // Act
-        let err = transport
-            .join_channel(2)
-            .expect_err("expected join to fail");
+        transport
+            .send_text(TextTarget::CurrentChannel, "hello".to_string())
+            .expect("send failed");
+
         // Assert
-        assert!(matches!(err, TransportError::Protocol(_)));
+        assert_eq!(
+            texts.borrow().as_slice(),
+            [TextMessageCommand {
+                channel_ids: vec![3],
+                tree_ids: Vec::new(),
+                session_ids: Vec::new(),
+                message: "hello".to_string(),
+            }]
+        );
+        assert!(matches!(
+            transport.take_events().as_slice(),
+            [super::TransportEvent::Text(TextMessage {
+                actor_id: Some(7),
+                ..
+            })]
+        ));
+    }
+
+    /// Tree and user targets map onto the matching protocol fields.
+    #[test]
+    fn send_text_maps_tree_and_user_targets() {
+        // Arrange
+        let (mut transport, texts) =
+            connected_with_session(vec![ControlMessage::ServerSync { session: 7 }]);
+
+        // Act
+        transport
+            .send_text(TextTarget::ChannelTree(0), "all".to_string())
+            .expect("send failed");
+        transport
+            .send_text(TextTarget::Users(vec![4, 5]), "dm".to_string())
+            .expect("send failed");
+
+        // Assert
+        let texts = texts.borrow();
+        assert_eq!(texts[0].tree_ids, vec![0]);
+        assert!(texts[0].channel_ids.is_empty());
+        assert_eq!(texts[1].session_ids, vec![4, 5]);
+    }
+
+    /// Sending without a known channel or with an empty body fails without side effects.
+    #[test]
+    fn send_text_rejects_invalid_requests() {
+        // Arrange
+        let (mut transport, texts) =
+            connected_with_session(vec![ControlMessage::ServerSync { session: 7 }]);
+
+        // Act
+        let no_channel = transport.send_text(TextTarget::CurrentChannel, "hi".to_string());
+        let empty = transport.send_text(TextTarget::Channel(1), "  ".to_string());
+
+        // Assert
+        assert!(matches!(no_channel, Err(TransportError::Protocol(_))));
+        assert!(matches!(empty, Err(TransportError::Protocol(_))));
+        assert!(texts.borrow().is_empty());
         assert!(transport.take_events().is_empty());
     }
 
-    /// Join sends the expected user state command to the control session.
+    /// A direct message without recipients is rejected and not echoed locally.
     #[test]
-    fn join_channel_sends_user_state_command() {
+    fn send_text_rejects_empty_user_list() {
+        // Arrange
+        let (mut transport, texts) =
+            connected_with_session(vec![ControlMessage::ServerSync { session: 7 }]);
+
+        // Act
+        let result = transport.send_text(TextTarget::Users(Vec::new()), "dm".to_string());
+
+        // Assert
+        assert!(matches!(result, Err(TransportError::Protocol(_))));
+        assert!(texts.borrow().is_empty());
+        assert!(transport.take_events().is_empty());
+    }
+
+    /// Sending text requires a connection.
+    #[test]
+    fn send_text_rejects_when_disconnected() {
+        // Arrange
+        let config = MumbleConfig::new("server".to_string(), DEFAULT_PORT, "tester".to_string());
+        let mut transport = MumbleTransport::new(config);
+
+        // Act
+        let err = transport
+            .send_text(TextTarget::Channel(1), "hi".to_string())
+            .expect_err("expected send to fail");
+
+        // Assert
+        assert!(matches!(err, TransportError::Disconnected));
+    }
+
+    /// Incoming text messages are emitted as text events.
+    #[test]
+    fn poll_emits_incoming_text() {
         // Arrange
         let config = MumbleConfig::new(
             "voice.example".to_string(),
             DEFAULT_PORT,
             "tester".to_string(),
         );
-        let capture = Rc::new(RefCell::new(None));
-        let commands = Rc::new(RefCell::new(Vec::new()));
-        let messages = vec![
-            ControlMessage::ServerSync { session: 7 },
-            ControlMessage::UserState {
-                id: 7,
-                name: "Self".to_string(),
-                channel_id: 1,
-                muted: false,
-                deafened: false,
-                talking: false,
-            },
-            ControlMessage::ChannelState {
-                id: 1,
-                name: "Lobby".to_string(),
-                parent_id: None,
-            },
-            ControlMessage::ChannelState {
-                id: 2,
-                name: "Ops".to_string(),
-                parent_id: None,
-            },
-        ];
+        let session = TestControlSession::new(Rc::new(RefCell::new(Vec::new())));
+        let incoming = Rc::clone(&session.incoming);
         let connector = TestControlConnectorWithSession {
-            last_request: Rc::clone(&capture),
-            messages,
-            session: TestControlSession::new(Rc::clone(&commands)),
+            last_request: Rc::new(RefCell::new(None)),
+            messages: Vec::new(),
+            session,
         };
         let mut transport = MumbleTransport::with_connector(config, Box::new(connector));
         transport.connect().expect("connect failed");
         transport.take_events();
+        let message = TextMessage {
+            actor_id: Some(3),
+            channel_ids: vec![1],
+            tree_ids: Vec::new(),
+            user_ids: Vec::new(),
+            message: "hey".to_string(),
+        };
+        incoming
+            .borrow_mut()
+            .push(Ok(ControlMessage::TextMessage(message.clone())));
 
         // Act
-        transport.join_channel(2).expect("join failed");
+        transport.poll().expect("poll failed");
 
         // Assert
-        let commands = commands.borrow();
-        assert_eq!(commands.len(), 1);
-        assert_eq!(
-            commands[0],
-            UserStateCommand {
-                session_id: 7,
-                channel_id: 2,
-                muted: None,
-                deafened: None,
+        assert!(matches!(
+            transport.take_events().as_slice(),
+            [super::TransportEvent::Text(text)] if *text == message
+        ));
+    }
+
+    struct TestRejectingConnector {
+        kind: RejectKind,
+    }
+
+    impl ControlConnector for TestRejectingConnector {
+        fn handshake(
+            &mut self,
+            _request: HandshakeRequest,
+        ) -> Result<ControlHandshake, TransportError> {
+            Err(TransportError::Rejected {
+                kind: self.kind,
+                reason: Some("Wrong server password".to_string()),
+            })
+        }
+    }
+
+    struct TestPinningConnector;
+
+    impl ControlConnector for TestPinningConnector {
+        fn handshake(
+            &mut self,
+            request: HandshakeRequest,
+        ) -> Result<ControlHandshake, TransportError> {
+            if request.trusted_fingerprint.as_deref() == Some("AA:BB") {
+                return Ok(ControlHandshake {
+                    messages: Vec::new(),
+                    session: None,
+                    tls: None,
+                    peer: None,
+                });
             }
-        );
+            Err(TransportError::UntrustedCertificate(UntrustedCertificate {
+                host: request.server,
+                port: request.port,
+                fingerprint: "AA:BB".to_string(),
+                pinned: request.trusted_fingerprint,
+            }))
+        }
+    }
+
+    struct TestTlsConnector {
+        tls: TlsSessionInfo,
+    }
+
+    impl ControlConnector for TestTlsConnector {
+        fn handshake(
+            &mut self,
+            _request: HandshakeRequest,
+        ) -> Result<ControlHandshake, TransportError> {
+            Ok(ControlHandshake {
+                messages: Vec::new(),
+                session: None,
+                tls: Some(self.tls.clone()),
+                peer: None,
+            })
+        }
     }
 
     struct TestControlConnectorWithMessages {
@@ -807,6 +2922,12 @@ mod tests {
 
     struct TestControlSession {
         commands: Rc<RefCell<Vec<UserStateCommand>>>,
+        texts: Rc<RefCell<Vec<TextMessageCommand>>>,
+        closed: Rc<RefCell<bool>>,
+        tokens: Rc<RefCell<Vec<Vec<SecretString>>>>,
+        crypt_setups: Rc<RefCell<Vec<Option<Vec<u8>>>>>,
+        voices: Rc<RefCell<Vec<VoicePacket<Serverbound>>>>,
+        incoming: Rc<RefCell<Vec<Result<ControlMessage, TransportError>>>>,
         fail: bool,
     }
 
@@ -814,6 +2935,12 @@ mod tests {
         fn new(commands: Rc<RefCell<Vec<UserStateCommand>>>) -> Self {
             Self {
                 commands,
+                texts: Rc::new(RefCell::new(Vec::new())),
+                closed: Rc::new(RefCell::new(false)),
+                tokens: Rc::new(RefCell::new(Vec::new())),
+                crypt_setups: Rc::new(RefCell::new(Vec::new())),
+                voices: Rc::new(RefCell::new(Vec::new())),
+                incoming: Rc::new(RefCell::new(Vec::new())),
                 fail: false,
             }
         }
@@ -828,6 +2955,8 @@ mod tests {
             Ok(ControlHandshake {
                 messages: self.messages.clone(),
                 session: None,
+                tls: None,
+                peer: None,
             })
         }
     }
@@ -837,13 +2966,23 @@ mod tests {
             &mut self,
             request: HandshakeRequest,
         ) -> Result<ControlHandshake, TransportError> {
+            // Like a real socket, the peer is whatever address the request named literally.
+            let peer = format!("{}:{}", request.server, request.port).parse().ok();
             *self.last_request.borrow_mut() = Some(request);
             Ok(ControlHandshake {
                 messages: self.messages.clone(),
                 session: Some(Box::new(TestControlSession {
                     commands: Rc::clone(&self.session.commands),
+                    texts: Rc::clone(&self.session.texts),
+                    closed: Rc::clone(&self.session.closed),
+                    tokens: Rc::clone(&self.session.tokens),
+                    crypt_setups: Rc::clone(&self.session.crypt_setups),
+                    voices: Rc::clone(&self.session.voices),
+                    incoming: Rc::clone(&self.session.incoming),
                     fail: self.session.fail,
                 })),
+                tls: None,
+                peer,
             })
         }
     }
@@ -856,5 +2995,53 @@ mod tests {
             self.commands.borrow_mut().push(command);
             Ok(())
         }
+
+        fn send_text(&mut self, command: TextMessageCommand) -> Result<(), TransportError> {
+            if self.fail {
+                return Err(TransportError::Protocol("send failed".to_string()));
+            }
+            self.texts.borrow_mut().push(command);
+            Ok(())
+        }
+
+        fn send_tokens(&mut self, tokens: Vec<SecretString>) -> Result<(), TransportError> {
+            if self.fail {
+                return Err(TransportError::Protocol("send failed".to_string()));
+            }
+            self.tokens.borrow_mut().push(tokens);
+            Ok(())
+        }
+
+        fn send_crypt_setup(
+            &mut self,
+            client_nonce: Option<Vec<u8>>,
+        ) -> Result<(), TransportError> {
+            if self.fail {
+                return Err(TransportError::Protocol("send failed".to_string()));
+            }
+            self.crypt_setups.borrow_mut().push(client_nonce);
+            Ok(())
+        }
+
+        fn send_voice(&mut self, packet: VoicePacket<Serverbound>) -> Result<(), TransportError> {
+            if self.fail {
+                return Err(TransportError::Protocol("send failed".to_string()));
+            }
+            self.voices.borrow_mut().push(packet);
+            Ok(())
+        }
+
+        fn try_recv(&mut self) -> Result<Option<ControlMessage>, TransportError> {
+            let mut incoming = self.incoming.borrow_mut();
+            if incoming.is_empty() {
+                return Ok(None);
+            }
+            incoming.remove(0).map(Some)
+        }
+
+        fn close(&mut self) -> Result<(), TransportError> {
+            *self.closed.borrow_mut() = true;
+            Ok(())
+        }
     }
 }
@@ -0,0 +1,636 @@
+use crate::mumble::config::MumbleConfig;
+use crate::mumble::known_hosts::host_key;
+use crate::mumble::secret::{SecretBytes, SecretString};
+use crate::transport::errors::TransportError;
+use openssl::hash::MessageDigest;
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const VAULT_FILE: &str = "vault.bin";
+const MACHINE_KEY_FILE: &str = "vault.key";
+const TEMP_SUFFIX: &str = ".tmp";
+const MAGIC: &[u8; 4] = b"BBV1";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 4 + SALT_LEN + NONCE_LEN + TAG_LEN;
+pub const PASSPHRASE_ITERATIONS: u32 = 600_000;
+const MAX_ITERATIONS: u32 = 10 * PASSPHRASE_ITERATIONS;
+
+type MachineId = fn() -> Result<SecretBytes, TransportError>;
+
+#[derive(Clone, Debug)]
+pub enum VaultKey {
+    Passphrase(SecretString),
+    Machine,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credentials {
+    pub password: Option<SecretString>,
+    #[serde(default)]
+    pub tokens: Vec<SecretString>,
+}
+
+#[derive(Debug)]
+pub struct CredentialVault {
+    path: PathBuf,
+    key: SecretBytes,
+    salt: [u8; SALT_LEN],
+    iterations: u32,
+    entries: BTreeMap<String, Credentials>,
+}
+
+impl CredentialVault {
+    pub fn open(app_data_dir: &Path, key: &VaultKey) -> Result<Self, TransportError> {
+        Self::open_with(app_data_dir, key, machine_id)
+    }
+
+    fn open_with(
+        app_data_dir: &Path,
+        key: &VaultKey,
+        machine_id: MachineId,
+    ) -> Result<Self, TransportError> {
+        let path = app_data_dir.join(VAULT_FILE);
+        let contents = match fs::read(&path) {
+            Ok(contents) => Some(contents),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        let Some(contents) = contents else {
+            let mut salt = [0u8; SALT_LEN];
+            rand_bytes(&mut salt).map_err(crypto_error)?;
+            return Ok(Self {
+                key: derive_key(app_data_dir, key, machine_id, &salt, PASSPHRASE_ITERATIONS)?,
+                path,
+                salt,
+                iterations: PASSPHRASE_ITERATIONS,
+                entries: BTreeMap::new(),
+            });
+        };
+
+        if contents.len() < HEADER_LEN || &contents[..MAGIC.len()] != MAGIC {
+            return Err(TransportError::InvalidConfig(
+                "credential vault is corrupt".to_string(),
+            ));
+        }
+        let (header, ciphertext) = contents.split_at(HEADER_LEN);
+        let mut offset = MAGIC.len();
+        let iterations = u32::from_be_bytes(
+            header[offset..offset + 4]
+                .try_into()
+                .expect("header length checked"),
+        );
+        offset += 4;
+        // Counts below what we write, or far above it, come from a tampered header and would
+        // either stall the key derivation or fail it with a confusing crypto error.
+        if !(PASSPHRASE_ITERATIONS..=MAX_ITERATIONS).contains(&iterations) {
+            return Err(TransportError::InvalidConfig(
+                "credential vault is corrupt".to_string(),
+            ));
+        }
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&header[offset..offset + SALT_LEN]);
+        offset += SALT_LEN;
+        let nonce = &header[offset..offset + NONCE_LEN];
+        let tag = &header[offset + NONCE_LEN..];
+
+        let derived = derive_key(app_data_dir, key, machine_id, &salt, iterations)?;
+        let plaintext = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            derived.expose(),
+            Some(nonce),
+            MAGIC,
+            ciphertext,
+            tag,
+        )
+        .map(SecretBytes::from)
+        .map_err(|_| {
+            TransportError::InvalidConfig("credential vault could not be unlocked".to_string())
+        })?;
+        let entries = serde_json::from_slice(plaintext.expose()).map_err(|err| {
+            TransportError::InvalidConfig(format!("invalid credential vault: {err}"))
+        })?;
+        Ok(Self {
+            path,
+            key: derived,
+            salt,
+            iterations,
+            entries,
+        })
+    }
+
+    pub fn credentials(&self, host: &str, port: u16) -> Option<&Credentials> {
+        self.entries.get(&host_key(host, port))
+    }
+
+    pub fn set_credentials(
+        &mut self,
+        host: &str,
+        port: u16,
+        credentials: Credentials,
+    ) -> Result<(), TransportError> {
+        let key = host_key(host, port);
+        if credentials == Credentials::default() {
+            self.entries.remove(&key);
+        } else {
+            self.entries.insert(key, credentials);
+        }
+        self.save()
+    }
+
+    pub fn set_tokens(
+        &mut self,
+        host: &str,
+        port: u16,
+        tokens: Vec<SecretString>,
+    ) -> Result<(), TransportError> {
+        let mut credentials = self.credentials(host, port).cloned().unwrap_or_default();
+        credentials.tokens = tokens;
+        self.set_credentials(host, port, credentials)
+    }
+
+    pub fn proxy_password(&self, host: &str, port: u16) -> Option<&SecretString> {
+        self.entries
+            .get(&proxy_key(host, port))
+            .and_then(|credentials| credentials.password.as_ref())
+    }
+
+    pub fn set_proxy_password(
+        &mut self,
+        host: &str,
+        port: u16,
+        password: SecretString,
+    ) -> Result<(), TransportError> {
+        self.entries.insert(
+            proxy_key(host, port),
+            Credentials {
+                password: Some(password),
+                tokens: Vec::new(),
+            },
+        );
+        self.save()
+    }
+
+    pub fn clear_proxy_password(&mut self, host: &str, port: u16) -> Result<(), TransportError> {
+        if self.entries.remove(&proxy_key(host, port)).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn apply_to(&self, config: &mut MumbleConfig) {
+        let Some(credentials) = self.credentials(&config.server, config.port) else {
+            return;
+        };
+        if credentials.password.is_some() {
+            config.password = credentials.password.clone();
+        }
+        if !credentials.tokens.is_empty() {
+            config.tokens = credentials.tokens.clone();
+        }
+    }
+
+    fn save(&self) -> Result<(), TransportError> {
+        let plaintext = serde_json::to_vec(&self.entries)
+            .map(SecretBytes::from)
+            .map_err(|err| {
+                TransportError::Io(format!("credential vault encoding failed: {err}"))
+            })?;
+        let mut nonce = [0u8; NONCE_LEN];
+        rand_bytes(&mut nonce).map_err(crypto_error)?;
+        let mut tag = [0u8; TAG_LEN];
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            self.key.expose(),
+            Some(&nonce),
+            MAGIC,
+            plaintext.expose(),
+            &mut tag,
+        )
+        .map_err(crypto_error)?;
+
+        let mut contents = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        contents.extend_from_slice(MAGIC);
+        contents.extend_from_slice(&self.iterations.to_be_bytes());
+        contents.extend_from_slice(&self.salt);
+        contents.extend_from_slice(&nonce);
+        contents.extend_from_slice(&tag);
+        contents.extend_from_slice(&ciphertext);
+        write_private(&self.path, &contents)
+    }
+}
+
+fn proxy_key(host: &str, port: u16) -> String {
+    // Server keys never contain a second bare colon, so proxy entries cannot collide with them.
+    format!("proxy:{}", host_key(host, port))
+}
+
+fn derive_key(
+    app_data_dir: &Path,
+    key: &VaultKey,
+    machine_id: MachineId,
+    salt: &[u8],
+    iterations: u32,
+) -> Result<SecretBytes, TransportError> {
+    let mut derived = SecretBytes::new(vec![0; KEY_LEN]);
+    match key {
+        VaultKey::Passphrase(passphrase) => {
+            if passphrase.is_empty() {
+                return Err(TransportError::InvalidConfig(
+                    "vault passphrase is required".to_string(),
+                ));
+            }
+            pbkdf2_hmac(
+                passphrase.expose().as_bytes(),
+                salt,
+                iterations as usize,
+                MessageDigest::sha256(),
+                derived.expose_mut(),
+            )
+            .map_err(crypto_error)?;
+        }
+        VaultKey::Machine => {
+            // The key file alone travels with a copied app data dir, so mix in the OS machine
+            // identifier; a copy then only opens on the machine that wrote it.
+            let machine_key = machine_key(app_data_dir)?;
+            let material =
+                SecretBytes::from([machine_key.expose(), machine_id()?.expose()].concat());
+            pbkdf2_hmac(
+                material.expose(),
+                salt,
+                1,
+                MessageDigest::sha256(),
+                derived.expose_mut(),
+            )
+            .map_err(crypto_error)?;
+        }
+    }
+    Ok(derived)
+}
+
+fn machine_key(app_data_dir: &Path) -> Result<SecretBytes, TransportError> {
+    let path = app_data_dir.join(MACHINE_KEY_FILE);
+    match fs::read(&path).map(SecretBytes::from) {
+        Ok(key) if key.len() == KEY_LEN => return Ok(key),
+        Ok(_) => {
+            return Err(TransportError::InvalidConfig(
+                "machine vault key is corrupt".to_string(),
+            ))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    let mut key = SecretBytes::new(vec![0; KEY_LEN]);
+    rand_bytes(key.expose_mut()).map_err(crypto_error)?;
+    write_private(&path, key.expose())?;
+    Ok(key)
+}
+
+fn machine_id() -> Result<SecretBytes, TransportError> {
+    let id = read_machine_id().unwrap_or_default();
+    let id = id.trim();
+    if id.is_empty() {
+        return Err(TransportError::InvalidConfig(
+            "no machine identifier available, use a passphrase for the vault".to_string(),
+        ));
+    }
+    Ok(SecretBytes::from(id.as_bytes().to_vec()))
+}
+
+#[cfg(target_os = "macos")]
+fn read_machine_id() -> Option<String> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.contains("\"IOPlatformUUID\""))
+        .and_then(|line| line.rsplit('"').nth(1))
+        .map(str::to_string)
+}
+
+#[cfg(windows)]
+fn read_machine_id() -> Option<String> {
+    let output = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKLM\SOFTWARE\Microsoft\Cryptography",
+            "/v",
+            "MachineGuid",
+        ])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.contains("MachineGuid"))
+        .and_then(|line| line.split_whitespace().last())
+        .map(str::to_string)
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn read_machine_id() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id", "/etc/hostid"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+}
+
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> Result<(), TransportError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Write beside the target and rename over it, so a crash mid-save keeps the old file intact.
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(TEMP_SUFFIX);
+    let temp = PathBuf::from(temp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&temp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(err) = written.and_then(|()| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+fn crypto_error(error: openssl::error::ErrorStack) -> TransportError {
+    TransportError::Io(format!("credential vault crypto failed: {error}"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{machine_id, CredentialVault, Credentials, VaultKey};
+    use crate::mumble::config::MumbleConfig;
+    use crate::mumble::identity::tests::TempDir;
+    use crate::mumble::secret::SecretBytes;
+    use crate::transport::errors::TransportError;
+    use std::path::Path;
+
+    fn test_machine_id() -> Result<SecretBytes, TransportError> {
+        Ok(SecretBytes::from(b"test-machine".to_vec()))
+    }
+
+    pub(crate) fn open_machine_vault(dir: &Path) -> Result<CredentialVault, TransportError> {
+        CredentialVault::open_with(dir, &VaultKey::Machine, test_machine_id)
+    }
+
+    fn credentials() -> Credentials {
+        Credentials {
+            password: Some("hunter2".into()),
+            tokens: vec!["red".into()],
+        }
+    }
+
+    /// Credentials saved under a passphrase reopen with it and stay encrypted on disk.
+    #[test]
+    fn passphrase_vault_round_trips() {
+        // Arrange
+        let dir = TempDir::new();
+        let key = VaultKey::Passphrase("correct horse".into());
+        let mut vault = CredentialVault::open(&dir.0, &key).expect("open failed");
+
+        // Act
+        vault
+            .set_credentials("voice.example", 64738, credentials())
+            .expect("save failed");
+        let reopened = CredentialVault::open(&dir.0, &key).expect("reopen failed");
+
+        // Assert
+        assert_eq!(
+            reopened.credentials("voice.example", 64738),
+            Some(&credentials())
+        );
+        let raw = std::fs::read(dir.0.join("vault.bin")).expect("read failed");
+        assert!(!raw.windows(7).any(|window| window == b"hunter2"));
+    }
+
+    /// A wrong passphrase cannot unlock the vault.
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut vault = CredentialVault::open(&dir.0, &VaultKey::Passphrase("right".into()))
+            .expect("open failed");
+        vault
+            .set_credentials("voice.example", 64738, credentials())
+            .expect("save failed");
+
+        // Act
+        let result = CredentialVault::open(&dir.0, &VaultKey::Passphrase("wrong".into()));
+
+        // Assert
+        assert!(matches!(result, Err(TransportError::InvalidConfig(_))));
+    }
+
+    /// The machine key is created once and reused to unlock the vault.
+    #[test]
+    fn machine_key_vault_round_trips() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut vault = open_machine_vault(&dir.0).expect("open failed");
+        vault
+            .set_credentials("voice.example", 64738, credentials())
+            .expect("save failed");
+
+        // Act
+        let reopened = open_machine_vault(&dir.0).expect("reopen failed");
+        std::fs::remove_file(dir.0.join("vault.key")).expect("remove failed");
+        let without_key = open_machine_vault(&dir.0);
+
+        // Assert
+        assert_eq!(
+            reopened.credentials("voice.example", 64738),
+            Some(&credentials())
+        );
+        assert!(matches!(without_key, Err(TransportError::InvalidConfig(_))));
+    }
+
+    /// A machine vault copied together with its key file does not open on another machine.
+    #[test]
+    fn machine_key_vault_is_bound_to_machine() {
+        // Arrange
+        let dir = TempDir::new();
+        let here = || Ok(SecretBytes::from(b"machine-a".to_vec()));
+        let elsewhere = || Ok(SecretBytes::from(b"machine-b".to_vec()));
+        let mut vault =
+            CredentialVault::open_with(&dir.0, &VaultKey::Machine, here).expect("open failed");
+        vault
+            .set_credentials("voice.example", 64738, credentials())
+            .expect("save failed");
+
+        // Act
+        let reopened =
+            CredentialVault::open_with(&dir.0, &VaultKey::Machine, here).expect("reopen failed");
+        let copied = CredentialVault::open_with(&dir.0, &VaultKey::Machine, elsewhere);
+
+        // Assert
+        assert_eq!(
+            reopened.credentials("voice.example", 64738),
+            Some(&credentials())
+        );
+        assert!(matches!(copied, Err(TransportError::InvalidConfig(_))));
+    }
+
+    /// Stored credentials fill the password and tokens of a matching config.
+    #[test]
+    fn apply_to_fills_config() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut vault = open_machine_vault(&dir.0).expect("open failed");
+        vault
+            .set_credentials("voice.example", 64738, credentials())
+            .expect("save failed");
+        let mut config = MumbleConfig::new("voice.example".to_string(), 64738, "alice".to_string());
+        let mut other = MumbleConfig::new("other.example".to_string(), 64738, "alice".to_string());
+
+        // Act
+        vault.apply_to(&mut config);
+        vault.apply_to(&mut other);
+
+        // Assert
+        assert_eq!(config.password, Some("hunter2".into()));
+        assert_eq!(config.tokens, vec!["red"]);
+        assert!(other.password.is_none());
+    }
+
+    /// Token updates keep the stored password and clearing everything drops the entry.
+    #[test]
+    fn set_tokens_keeps_password() {
+        // Arrange
+        let dir = TempDir::new();
+        let mut vault = open_machine_vault(&dir.0).expect("open failed");
+        vault
+            .set_credentials("voice.example", 64738, credentials())
+            .expect("save failed");
+
+        // Act
+        vault
+            .set_tokens("Voice.Example", 64738, vec!["blue".into()])
+            .expect("save failed");
+        let updated = vault.credentials("voice.example", 64738).cloned();
+        vault
+            .set_credentials("voice.example", 64738, Credentials::default())
+            .expect("save failed");
+        vault
+            .set_tokens("voice.example", 64738, Vec::new())
+            .expect("save failed");
+
+        // Assert
+        assert_eq!(
+            updated,
+            Some(Credentials {
+                password: Some("hunter2".into()),
+                tokens: vec!["blue".into()],
+            })
+        );
+        assert_eq!(vault.credentials("voice.example", 64738), None);
+    }
+
+    /// Saves replace the vault through a temp file, so a leftover partial write is harmless.
+    #[test]
+    fn save_replaces_vault_atomically() {
+        // Arrange
+        let dir = TempDir::new();
+        let key = VaultKey::Passphrase("correct horse".into());
+        let mut vault = CredentialVault::open(&dir.0, &key).expect("open failed");
+        vault
+            .set_credentials("voice.example", 64738, credentials())
+            .expect("save failed");
+        std::fs::write(dir.0.join("vault.bin.tmp"), b"BBV1trunc").expect("write failed");
+
+        // Act
+        let reopened = CredentialVault::open(&dir.0, &key).expect("reopen failed");
+        vault
+            .set_credentials("other.example", 64738, credentials())
+            .expect("save failed");
+
+        // Assert
+        assert_eq!(
+            reopened.credentials("voice.example", 64738),
+            Some(&credentials())
+        );
+        assert!(!dir.0.join("vault.bin.tmp").exists());
+        let reopened = CredentialVault::open(&dir.0, &key).expect("reopen failed");
+        assert!(reopened.credentials("other.example", 64738).is_some());
+    }
+
+    /// Headers with an iteration count we never write are reported as corrupt before deriving.
+    #[test]
+    fn open_rejects_tampered_iterations() {
+        // Arrange
+        let dir = TempDir::new();
+        let key = VaultKey::Passphrase("secret".into());
+        let mut vault = CredentialVault::open(&dir.0, &key).expect("open failed");
+        vault
+            .set_credentials("voice.example", 64738, credentials())
+            .expect("save failed");
+        let path = dir.0.join("vault.bin");
+        let original = std::fs::read(&path).expect("read failed");
+
+        // Act
+        let results: Vec<_> = [0u32, u32::MAX]
+            .into_iter()
+            .map(|iterations| {
+                let mut tampered = original.clone();
+                tampered[4..8].copy_from_slice(&iterations.to_be_bytes());
+                std::fs::write(&path, tampered).expect("write failed");
+                CredentialVault::open(&dir.0, &key)
+            })
+            .collect();
+
+        // Assert
+        for result in results {
+            assert!(
+                matches!(result, Err(TransportError::InvalidConfig(ref reason)) if reason.contains("corrupt"))
+            );
+        }
+    }
+
+    /// Files that are not vaults are reported as corrupt.
+    #[test]
+    fn open_rejects_corrupt_file() {
+        // Arrange
+        let dir = TempDir::new();
+        std::fs::write(dir.0.join("vault.bin"), b"garbage").expect("write failed");
+
+        // Act
+        let result = open_machine_vault(&dir.0);
+
+        // Assert
+        assert!(matches!(result, Err(TransportError::InvalidConfig(_))));
+    }
+
+    /// The machine key uses the OS machine identifier, or refuses to open without one.
+    #[test]
+    fn open_uses_os_machine_id() {
+        // Arrange
+        let dir = TempDir::new();
+
+        // Act
+        let result = CredentialVault::open(&dir.0, &VaultKey::Machine);
+
+        // Assert
+        match machine_id() {
+            Ok(_) => assert!(result.is_ok()),
+            Err(_) => assert!(matches!(result, Err(TransportError::InvalidConfig(_)))),
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use crate::mumble::crypt::{CryptStats, VoiceCrypt};
+use crate::transport::errors::TransportError;
+use mumble_protocol_2x::crypt::MAX_PACKET_SIZE;
+use mumble_protocol_2x::voice::{Clientbound, Serverbound, VoicePacket};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+pub const UDP_FALLBACK_PINGS: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoicePath {
+    Udp,
+    Tunnel,
+}
+
+#[derive(Debug)]
+pub struct VoiceChannel {
+    socket: UdpSocket,
+    crypt: VoiceCrypt,
+    ping_interval: Duration,
+    epoch: Instant,
+    next_ping: Instant,
+    last_pong: Option<Instant>,
+    rtt: Option<Duration>,
+    resync_requested: bool,
+}
+
+impl VoiceChannel {
+    pub fn open(
+        server: SocketAddr,
+        crypt: VoiceCrypt,
+        ping_interval: Duration,
+        now: Instant,
+    ) -> Result<Self, TransportError> {
+        let bind: SocketAddr = if server.is_ipv6() {
+            "[::]:0".parse().expect("valid bind address")
+        } else {
+            "0.0.0.0:0".parse().expect("valid bind address")
+        };
+        let socket = UdpSocket::bind(bind)?;
+        socket.connect(server)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            crypt,
+            ping_interval,
+            epoch: now,
+            next_ping: now,
+            last_pong: None,
+            rtt: None,
+            resync_requested: false,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, TransportError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    pub fn crypt(&self) -> &VoiceCrypt {
+        &self.crypt
+    }
+
+    pub fn crypt_mut(&mut self) -> &mut VoiceCrypt {
+        &mut self.crypt
+    }
+
+    pub fn stats(&self) -> CryptStats {
+        self.crypt.stats()
+    }
+
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    pub fn last_pong(&self) -> Option<Instant> {
+        self.last_pong
+    }
+
+    pub fn take_resync_request(&mut self) -> bool {
+        std::mem::take(&mut self.resync_requested)
+    }
+
+    pub fn udp_alive(&self, now: Instant) -> bool {
+        // UDP counts as working while pongs keep arriving within a few ping intervals.
+        let window = self.ping_interval * UDP_FALLBACK_PINGS;
+        self.last_pong
+            .is_some_and(|pong| now.saturating_duration_since(pong) <= window)
+    }
+
+    pub fn send(&mut self, packet: VoicePacket<Serverbound>) -> Result<(), TransportError> {
+        let datagram = self.crypt.encrypt(packet);
+        match self.socket.send(&datagram) {
+            Ok(_) => Ok(()),
+            // The server may simply not be listening yet; pings keep retrying.
+            Err(err) if err.kind() == ErrorKind::ConnectionRefused => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn poll(&mut self, now: Instant) -> Result<Vec<VoicePacket<Clientbound>>, TransportError> {
+        if now >= self.next_ping {
+            self.next_ping = now + self.ping_interval;
+            // Pings also tell the server which address to send our voice to.
+            let timestamp = now.saturating_duration_since(self.epoch).as_micros() as u64;
+            self.send(VoicePacket::Ping { timestamp })?;
+        }
+
+        let mut packets = Vec::new();
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        loop {
+            let len = match self.socket.recv(&mut buffer) {
+                Ok(len) => len,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(packets),
+                Err(err) if err.kind() == ErrorKind::ConnectionRefused => return Ok(packets),
+                Err(err) => return Err(err.into()),
+            };
+            // Undecryptable datagrams are counted as failed and may call for a resync.
+            match self.crypt.decrypt(&buffer[..len], now) {
+                Ok(VoicePacket::Ping { timestamp }) => {
+                    let sent_at = self.epoch + Duration::from_micros(timestamp);
+                    if let Some(rtt) = now.checked_duration_since(sent_at) {
+                        self.rtt = Some(rtt);
+                        self.last_pong = Some(now);
+                    }
+                }
+                Ok(packet) => packets.push(packet),
+                // A quiet or blocked path is not a reason to resync; only a bad datagram is.
+                Err(_) => {
+                    if self.crypt.resync_due(now) {
+                        self.resync_requested = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VoiceChannel;
+    use crate::mumble::crypt::tests::{audio, client_crypt, server_state};
+    use crate::mumble::crypt::RESYNC_AFTER;
+    use bytes::{Bytes, BytesMut};
+    use mumble_protocol_2x::voice::{VoicePacket, VoicePacketPayload};
+    use std::net::UdpSocket;
+    use std::time::{Duration, Instant};
+
+    fn recv_from_client(server: &UdpSocket) -> (BytesMut, std::net::SocketAddr) {
+        let mut buffer = [0u8; 1024];
+        let (len, client) = server.recv_from(&mut buffer).expect("recv failed");
+        (BytesMut::from(&buffer[..len]), client)
+    }
+
+    fn poll_until<T>(
+        channel: &mut VoiceChannel,
+        mut done: impl FnMut(
+            &mut VoiceChannel,
+            Vec<VoicePacket<mumble_protocol_2x::voice::Clientbound>>,
+        ) -> Option<T>,
+    ) -> T {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while Instant::now() < deadline {
+            let packets = channel.poll(Instant::now()).expect("poll failed");
+            if let Some(result) = done(channel, packets) {
+                return result;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("voice channel did not receive in time");
+    }
+
+    /// Pings go out encrypted on open and their echo yields a round trip time.
+    #[test]
+    fn poll_pings_and_measures_rtt() {
+        // Arrange
+        let server = UdpSocket::bind("127.0.0.1:0").expect("bind failed");
+        let mut server_crypt = server_state();
+        let now = Instant::now();
+        let mut channel = VoiceChannel::open(
+            server.local_addr().expect("missing address"),
+            client_crypt(now),
+            Duration::from_secs(5),
+            now,
+        )
+        .expect("open failed");
+
+        // Act
+        channel.poll(now).expect("poll failed");
+        let (mut datagram, client) = recv_from_client(&server);
+        let ping = server_crypt
+            .decrypt(&mut datagram)
+            .expect("decrypt failed")
+            .expect("parse failed");
+        let mut echo = BytesMut::new();
+        let VoicePacket::Ping { timestamp } = ping else {
+            panic!("expected ping, got {ping:?}");
+        };
+        server_crypt.encrypt(VoicePacket::Ping { timestamp }, &mut echo);
+        server.send_to(&echo, client).expect("send failed");
+        let rtt = poll_until(&mut channel, |channel, _| channel.rtt());
+
+        // Assert
+        assert!(rtt < Duration::from_secs(2));
+        assert!(channel.last_pong().is_some());
+        assert!(channel.udp_alive(Instant::now()));
+        assert!(!channel.udp_alive(Instant::now() + Duration::from_secs(16)));
+        assert_eq!(channel.stats().good, 1);
+    }
+
+    /// Audio from the server is decrypted and returned while garbage is only counted.
+    #[test]
+    fn poll_returns_audio_packets() {
+        // Arrange
+        let server = UdpSocket::bind("127.0.0.1:0").expect("bind failed");
+        let mut server_crypt = server_state();
+        let now = Instant::now();
+        let mut channel = VoiceChannel::open(
+            server.local_addr().expect("missing address"),
+            client_crypt(now),
+            Duration::from_secs(5),
+            now,
+        )
+        .expect("open failed");
+        channel.poll(now).expect("poll failed");
+        let (_, client) = recv_from_client(&server);
+        let mut sealed = BytesMut::new();
+        server_crypt.encrypt(audio(4, 1), &mut sealed);
+
+        // Act
+        server.send_to(b"garbage!", client).expect("send failed");
+        server.send_to(&sealed, client).expect("send failed");
+        let packets = poll_until(&mut channel, |_, packets| {
+            (!packets.is_empty()).then_some(packets)
+        });
+
+        // Assert
+        assert_eq!(packets, vec![audio(4, 1)]);
+        assert_eq!(channel.stats().good, 1);
+        assert_eq!(channel.stats().failed, 1);
+    }
+
+    /// Silence on the UDP path never asks for a resync, a stale undecryptable datagram does.
+    #[test]
+    fn poll_requests_resync_only_after_failed_decrypt() {
+        // Arrange
+        let server = UdpSocket::bind("127.0.0.1:0").expect("bind failed");
+        let now = Instant::now();
+        let mut channel = VoiceChannel::open(
+            server.local_addr().expect("missing address"),
+            client_crypt(now),
+            Duration::from_secs(5),
+            now,
+        )
+        .expect("open failed");
+        channel.poll(now).expect("poll failed");
+        let (_, client) = recv_from_client(&server);
+        let stale = now + RESYNC_AFTER * 4;
+
+        // Act
+        let mut quiet = false;
+        for step in 1..=4 {
+            channel
+                .poll(now + RESYNC_AFTER * step)
+                .expect("poll failed");
+            quiet |= channel.take_resync_request();
+        }
+        server.send_to(b"garbage!", client).expect("send failed");
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while channel.stats().failed == 0 && Instant::now() < deadline {
+            channel.poll(stale).expect("poll failed");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let requested = channel.take_resync_request();
+        let repeated = channel.take_resync_request();
+
+        // Assert
+        assert!(!quiet);
+        assert!(requested);
+        assert!(!repeated);
+    }
+
+    /// Outgoing audio is sealed with the client nonce.
+    #[test]
+    fn send_encrypts_audio() {
+        // Arrange
+        let server = UdpSocket::bind("127.0.0.1:0").expect("bind failed");
+        let mut server_crypt = server_state();
+        let now = Instant::now();
+        let mut channel = VoiceChannel::open(
+            server.local_addr().expect("missing address"),
+            client_crypt(now),
+            Duration::from_secs(5),
+            now,
+        )
+        .expect("open failed");
+        let packet = VoicePacket::Audio {
+            _dst: std::marker::PhantomData,
+            target: 0,
+            session_id: (),
+            seq_num: 8,
+            payload: VoicePacketPayload::Opus(Bytes::from_static(b"frame"), true),
+            position_info: None,
+        };
+
+        // Act
+        channel.send(packet.clone()).expect("send failed");
+        let (mut datagram, _) = recv_from_client(&server);
+        let received = server_crypt
+            .decrypt(&mut datagram)
+            .expect("decrypt failed")
+            .expect("parse failed");
+
+        // Assert
+        assert_eq!(received, packet);
+    }
+}
@@ -1,11 +1,72 @@
+use crate::transport::types::UntrustedCertificate;
 use std::fmt;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RejectKind {
+    Unknown,
+    WrongVersion,
+    InvalidUsername,
+    WrongUserPW,
+    WrongServerPW,
+    UsernameInUse,
+    ServerFull,
+    NoCertificate,
+    AuthenticatorFail,
+}
+
+impl fmt::Display for RejectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            RejectKind::Unknown => "unknown reason",
+            RejectKind::WrongVersion => "incompatible version",
+            RejectKind::InvalidUsername => "invalid username",
+            RejectKind::WrongUserPW => "wrong user password",
+            RejectKind::WrongServerPW => "wrong server password",
+            RejectKind::UsernameInUse => "username in use",
+            RejectKind::ServerFull => "server full",
+            RejectKind::NoCertificate => "certificate required",
+            RejectKind::AuthenticatorFail => "authenticator failure",
+        };
+        write!(f, "{text}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectPhase {
+    Resolve,
+    Tcp,
+    Proxy,
+    Tls,
+}
+
+impl fmt::Display for ConnectPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            ConnectPhase::Resolve => "address resolution",
+            ConnectPhase::Tcp => "tcp connect",
+            ConnectPhase::Proxy => "proxy negotiation",
+            ConnectPhase::Tls => "tls handshake",
+        };
+        write!(f, "{text}")
+    }
+}
+
 #[derive(Debug)]
 pub enum TransportError {
     Disconnected,
     Protocol(String),
     InvalidConfig(String),
     Io(String),
+    Timeout,
+    Rejected {
+        kind: RejectKind,
+        reason: Option<String>,
+    },
+    UntrustedCertificate(UntrustedCertificate),
+    Connect {
+        phase: ConnectPhase,
+        message: String,
+    },
 }
 
 impl fmt::Display for TransportError {
@@ -15,6 +76,24 @@ impl fmt::Display for TransportError {
             TransportError::Protocol(message) => write!(f, "protocol error: {message}"),
             TransportError::InvalidConfig(message) => write!(f, "invalid config: {message}"),
             TransportError::Io(message) => write!(f, "io error: {message}"),
+            TransportError::Timeout => write!(f, "operation timed out"),
+            TransportError::Rejected { kind, reason } => match reason {
+                Some(reason) => write!(f, "rejected by server ({kind}): {reason}"),
+                None => write!(f, "rejected by server ({kind})"),
+            },
+            TransportError::Connect { phase, message } => write!(f, "{phase} failed: {message}"),
+            TransportError::UntrustedCertificate(certificate) => match &certificate.pinned {
+                Some(pinned) => write!(
+                    f,
+                    "server certificate for {}:{} changed (pinned {pinned}, got {})",
+                    certificate.host, certificate.port, certificate.fingerprint
+                ),
+                None => write!(
+                    f,
+                    "untrusted server certificate for {}:{} ({})",
+                    certificate.host, certificate.port, certificate.fingerprint
+                ),
+            },
         }
     }
 }
@@ -23,13 +102,19 @@ impl std::error::Error for TransportError {}
 
 impl From<std::io::Error> for TransportError {
     fn from(error: std::io::Error) -> Self {
-        TransportError::Io(error.to_string())
+        match error.kind() {
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                TransportError::Timeout
+            }
+            _ => TransportError::Io(error.to_string()),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::TransportError;
+    use super::{ConnectPhase, RejectKind, TransportError};
+    use crate::transport::types::UntrustedCertificate;
     use std::io;
 
     /// Each error variant renders the expected display text.
@@ -53,11 +138,73 @@ mod tests {
             TransportError::Io("disk".to_string()).to_string(),
             "io error: disk"
         );
+        assert_eq!(TransportError::Timeout.to_string(), "operation timed out");
+        assert_eq!(
+            TransportError::Rejected {
+                kind: RejectKind::WrongServerPW,
+                reason: Some("Wrong password".to_string()),
+            }
+            .to_string(),
+            "rejected by server (wrong server password): Wrong password"
+        );
+        assert_eq!(
+            TransportError::Rejected {
+                kind: RejectKind::ServerFull,
+                reason: None,
+            }
+            .to_string(),
+            "rejected by server (server full)"
+        );
+        assert_eq!(
+            TransportError::UntrustedCertificate(UntrustedCertificate {
+                host: "voice.example".to_string(),
+                port: 64738,
+                fingerprint: "AA:BB".to_string(),
+                pinned: None,
+            })
+            .to_string(),
+            "untrusted server certificate for voice.example:64738 (AA:BB)"
+        );
+        assert_eq!(
+            TransportError::UntrustedCertificate(UntrustedCertificate {
+                host: "voice.example".to_string(),
+                port: 64738,
+                fingerprint: "AA:BB".to_string(),
+                pinned: Some("CC:DD".to_string()),
+            })
+            .to_string(),
+            "server certificate for voice.example:64738 changed (pinned CC:DD, got AA:BB)"
+        );
+        assert_eq!(
+            TransportError::Connect {
+                phase: ConnectPhase::Resolve,
+                message: "no addresses".to_string(),
+            }
+            .to_string(),
+            "address resolution failed: no addresses"
+        );
+        assert_eq!(
+            TransportError::Connect {
+                phase: ConnectPhase::Proxy,
+                message: "407 Proxy Authentication Required".to_string(),
+            }
+            .to_string(),
+            "proxy negotiation failed: 407 Proxy Authentication Required"
+        );
+        assert_eq!(
+            TransportError::Connect {
+                phase: ConnectPhase::Tls,
+                message: "bad record".to_string(),
+            }
+            .to_string(),
+            "tls handshake failed: bad record"
+        );
         // Assert
     }
 
     /// Mapping an IO error yields the `Io` transport variant.
     #[test]
+    #[allow(clippy::io_other_error)]
     fn from_io_error_maps_to_io_variant() {
         // Arrange
         let error = io::Error::new(io::ErrorKind::Other, "broken");
@@ -66,4 +213,15 @@ mod tests {
         // Assert
         assert_eq!(mapped.to_string(), "io error: broken");
     }
+
+    /// Read timeouts map to the `Timeout` variant so pollers can tell idle from failure.
+    #[test]
+    fn from_io_timeout_maps_to_timeout_variant() {
+        // Arrange
+        let error = io::Error::new(io::ErrorKind::WouldBlock, "idle");
+        // Act
+        let mapped = TransportError::from(error);
+        // Assert
+        assert!(matches!(mapped, TransportError::Timeout));
+    }
 }
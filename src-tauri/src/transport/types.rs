@@ -13,6 +13,11 @@ pub struct User {
     pub muted: bool,
     pub deafened: bool,
     pub talking: bool,
+    pub mute: bool,
+    pub deaf: bool,
+    pub suppress: bool,
+    pub priority_speaker: bool,
+    pub recording: bool,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -22,3 +27,64 @@ pub enum ConnState {
     Connected,
     Error,
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProtocolVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl ProtocolVersion {
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    pub fn from_v1(version: u32) -> Self {
+        Self::new(
+            (version >> 16) as u16,
+            ((version >> 8) & 0xff) as u16,
+            (version & 0xff) as u16,
+        )
+    }
+
+    pub fn from_v2(version: u64) -> Self {
+        Self::new(
+            (version >> 48) as u16,
+            (version >> 32) as u16,
+            (version >> 16) as u16,
+        )
+    }
+
+    pub fn to_v1(self) -> u32 {
+        (u32::from(self.major) << 16)
+            | (u32::from(self.minor.min(0xff)) << 8)
+            | u32::from(self.patch.min(0xff))
+    }
+
+    pub fn to_v2(self) -> u64 {
+        (u64::from(self.major) << 48)
+            | (u64::from(self.minor) << 32)
+            | (u64::from(self.patch) << 16)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub version: Option<ProtocolVersion>,
+    pub release: Option<String>,
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UntrustedCertificate {
+    pub host: String,
+    pub port: u16,
+    pub fingerprint: String,
+    pub pinned: Option<String>,
+}